@@ -1,52 +1,122 @@
-use std::io::{BufRead, BufReader, BufWriter, Write};
-use std::net::TcpStream;
-use std::str::SplitWhitespace;
+use std::path::Path;
+use std::str::{FromStr, SplitWhitespace};
+use std::time::Duration;
 
+use crate::error::{Error, Result};
 use crate::game::base::{Color, GameResult};
-use crate::game::square::Square;
-use crate::message::ServerMessage;
+use crate::game::moves::Move;
+use crate::message::{move_message, ServerMessage};
+use crate::transport::{Direction, ReplayTransport, SessionLog, TcpTransport, Transport};
 
 pub struct Client {
-    reader: BufReader<TcpStream>,
-    writer: BufWriter<TcpStream>,
+    transport: Box<dyn Transport>,
+    log: Option<SessionLog>,
+}
+
+// How many times and how often to try reconnecting after the connection drops.
+#[derive(Clone, Copy, Debug)]
+pub struct ReconnectPolicy {
+    pub max_attempts: u32,
+    pub interval: Duration,
+}
+
+impl ReconnectPolicy {
+    pub fn new(max_attempts: u32, interval: Duration) -> Self {
+        Self {
+            max_attempts,
+            interval,
+        }
+    }
+
+    pub fn never() -> Self {
+        Self::new(0, Duration::from_millis(0))
+    }
 }
 
 impl Client {
-    pub fn new(host: &str, port: u32) -> Self {
-        let addr = host.to_string() + ":" + &port.to_string();
-        let r_stream =
-            TcpStream::connect(addr).expect(&format!("Couldn't connect to {}:{}", host, port));
-        let w_stream = r_stream.try_clone().expect("Couldn't clone stream.");
+    pub fn new(host: &str, port: u32) -> Result<Self> {
+        Ok(Self::with_transport(Box::new(TcpTransport::connect(
+            host, port,
+        )?)))
+    }
+
+    pub fn replay<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self::with_transport(Box::new(ReplayTransport::open(path)?)))
+    }
+
+    pub fn with_transport(transport: Box<dyn Transport>) -> Self {
         Self {
-            reader: BufReader::new(r_stream),
-            writer: BufWriter::new(w_stream),
+            transport,
+            log: None,
         }
     }
 
-    pub fn poll_message(&mut self) -> Result<ServerMessage, String> {
+    pub fn record_session<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        self.log = Some(SessionLog::create(path)?);
+        Ok(())
+    }
+
+    pub fn reconnect(&mut self) -> Result<()> {
+        self.transport.reconnect()
+    }
+
+    pub fn poll_message(&mut self) -> Result<ServerMessage> {
         loop {
             let mut buf = String::new();
-            if self.reader.read_line(&mut buf).is_err() {
-                return Err("Error occured while reading message.".to_string());
+            if self.transport.read_line(&mut buf)? == 0 {
+                return Err(Error::ConnectionClosed);
             }
-            if buf.len() == 0 {
+            if buf.trim().is_empty() {
                 continue;
             }
             print!("Read: {}", buf);
+            self.record(Direction::Received, &buf);
             return self.parse_input(buf);
         }
     }
 
-    pub fn send_message(&mut self, msg: &str) -> Result<(), String> {
-        writeln!(self.writer, "{}", msg)
-            .map_err(|_| "Couldn't send.".to_string())
-            .map(|_| {
-                self.writer.flush().unwrap();
-                println!("Sent {}", msg);
-            })
+    pub fn send_message(&mut self, msg: &str) -> Result<()> {
+        self.deliver(msg).map(|_| ())
     }
 
-    pub fn parse_input(&self, buf: String) -> Result<ServerMessage, String> {
+    // Sends a move and returns the move the server received, which only
+    // differs from `pos` when replaying a recorded session.
+    pub fn send_move(&mut self, pos: Move) -> Result<Move> {
+        let msg = move_message(pos);
+        let delivered = self.deliver(&msg)?;
+        if delivered == msg {
+            return Ok(pos);
+        }
+        match self.parse_input(delivered)? {
+            ServerMessage::Move { pos } => Ok(pos),
+            _ => Err(Error::UnexpectedMessage { expected: "Move" }),
+        }
+    }
+
+    fn deliver(&mut self, msg: &str) -> Result<String> {
+        let delivered = self.transport.write_line(msg)?;
+        println!("Sent {}", msg);
+        self.record(Direction::Sent, &delivered);
+        Ok(delivered)
+    }
+
+    // A failing log is dropped rather than reported, as an I/O error here
+    // would be taken for a lost connection.
+    fn record(&mut self, direction: Direction, line: &str) {
+        if let Some(log) = self.log.as_mut() {
+            if let Err(e) = log.record(direction, line) {
+                eprintln!("Session log disabled: {}", e);
+                self.log = None;
+            }
+        }
+    }
+
+    // Moves that differed from the recorded ones when replaying a session.
+    pub fn replay_mismatches(&self) -> usize {
+        self.transport.mismatch_count()
+    }
+
+    pub fn parse_input(&self, buf: String) -> Result<ServerMessage> {
         let mut split = buf.split_whitespace();
         match split.next() {
             Some(cmd) => match cmd {
@@ -55,34 +125,37 @@ impl Client {
                 "MOVE" => self.parse_move(&mut split),
                 "ACK" => self.parse_ack(&mut split),
                 "BYE" => self.parse_bye(&mut split),
-                _ => Err("Invalid command".to_string()),
+                _ => Err(Error::UnknownCommand(cmd.to_string())),
             },
-            None => Err("test".to_string()),
+            None => Err(malformed("Empty message.")),
         }
     }
 
-    fn parse_start(&self, split: &mut SplitWhitespace) -> Result<ServerMessage, String> {
+    fn parse_start(&self, split: &mut SplitWhitespace) -> Result<ServerMessage> {
         let color = match split.next() {
             Some(cmd) => match cmd {
                 "BLACK" => Color::Dark,
                 "WHITE" => Color::Light,
-                _ => return Err("While parsing start: Invalid color.".to_string()),
+                _ => return Err(malformed("While parsing start: Invalid color.")),
             },
-            None => return Err("While parsing start: Invalid message.".to_string()),
+            None => return Err(malformed("While parsing start: Invalid message.")),
         };
         let op_name = match split.next() {
             Some(name) => name,
-            None => return Err("While parsing start: Invalid message.".to_string()),
+            None => return Err(malformed("While parsing start: Invalid message.")),
         }
         .to_string();
         let remaining_time_ms = match split.next() {
             Some(time) => match time.parse() {
                 Ok(i) => i,
                 Err(s) => {
-                    return Err(format!("While parsing start: Invalid time: {}", s).to_string())
+                    return Err(Error::MalformedMessage(format!(
+                        "While parsing start: Invalid time: {}",
+                        s
+                    )))
                 }
             },
-            None => return Err("While parsing start: Invalid message.".to_string()),
+            None => return Err(malformed("While parsing start: Invalid message.")),
         };
         Ok(ServerMessage::Start {
             color,
@@ -91,33 +164,33 @@ impl Client {
         })
     }
 
-    fn parse_end(&self, split: &mut SplitWhitespace) -> Result<ServerMessage, String> {
+    fn parse_end(&self, split: &mut SplitWhitespace) -> Result<ServerMessage> {
         let result = match split.next() {
             Some(r) => match r {
                 "WIN" => GameResult::Win,
                 "LOSE" => GameResult::Lose,
                 "TIE" => GameResult::Tie,
-                _ => return Err("While parsing end: Invalid result.".to_string()),
+                _ => return Err(malformed("While parsing end: Invalid result.")),
             },
-            None => return Err("While parsing end: Invalid message.".to_string()),
+            None => return Err(malformed("While parsing end: Invalid message.")),
         };
         let player_count = match split.next() {
             Some(n) => match n.parse() {
                 Ok(i) => i,
-                Err(_) => return Err("While parsing end: Invalid count.".to_string()),
+                Err(_) => return Err(malformed("While parsing end: Invalid count.")),
             },
-            None => return Err("While parsing end: Invalid message.".to_string()),
+            None => return Err(malformed("While parsing end: Invalid message.")),
         };
         let op_count = match split.next() {
             Some(n) => match n.parse() {
                 Ok(i) => i,
-                Err(_) => return Err("While parsing end: Invalid count.".to_string()),
+                Err(_) => return Err(malformed("While parsing end: Invalid count.")),
             },
-            None => return Err("While parsing end: Invalid message.".to_string()),
+            None => return Err(malformed("While parsing end: Invalid message.")),
         };
         let reason = match split.next() {
             Some(s) => s,
-            None => return Err("While parsing end: Invalid message.".to_string()),
+            None => return Err(malformed("While parsing end: Invalid message.")),
         }
         .to_string();
         Ok(ServerMessage::End {
@@ -128,39 +201,59 @@ impl Client {
         })
     }
 
-    fn parse_move(&self, split: &mut SplitWhitespace) -> Result<ServerMessage, String> {
+    fn parse_move(&self, split: &mut SplitWhitespace) -> Result<ServerMessage> {
         let pos = match split.next() {
             Some(s) => s,
-            None => return Err("While parsing move: Invalid message.".to_string()),
+            None => return Err(malformed("While parsing move: Invalid message.")),
         };
-        let pos = if pos == "PASS" {
-            None
-        } else {
-            match Square::from_str(pos) {
-                Ok(p) => Some(p),
-                Err(s) => return Err(s.to_string()),
-            }
+        let pos = match Move::from_str(pos) {
+            Ok(mv) => mv,
+            Err(_) => return Err(Error::BadSquare(pos.to_string())),
         };
         Ok(ServerMessage::Move { pos })
     }
 
-    fn parse_ack(&self, split: &mut SplitWhitespace) -> Result<ServerMessage, String> {
+    fn parse_ack(&self, split: &mut SplitWhitespace) -> Result<ServerMessage> {
         split
             .next()
-            .ok_or("While parsing ack: Invalid message.".to_string())
+            .ok_or_else(|| malformed("While parsing ack: Invalid message."))
             .and_then(|s| {
                 s.parse()
-                    .map_err(|_| "While parsing ack: Invalid time.".to_string())
+                    .map_err(|_| malformed("While parsing ack: Invalid time."))
             })
             .map(|remaining_time_ms| ServerMessage::Ack { remaining_time_ms })
     }
 
-    fn parse_bye(&self, split: &mut SplitWhitespace) -> Result<ServerMessage, String> {
+    fn parse_bye(&self, split: &mut SplitWhitespace) -> Result<ServerMessage> {
         split
             .next()
-            .ok_or("While parsing bye: Invalid message.".to_string())
+            .ok_or_else(|| malformed("While parsing bye: Invalid message."))
             .map(|s| ServerMessage::Bye {
                 stat: s.to_string(),
             })
     }
 }
+
+fn malformed(msg: &str) -> Error {
+    Error::MalformedMessage(msg.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn replay_mismatches() {
+        let transport = ReplayTransport::new(vec![
+            (Direction::Sent, "OPEN test".to_string()),
+            (Direction::Sent, "MOVE F5".to_string()),
+        ]);
+        let mut client = Client::with_transport(Box::new(transport));
+        client.send_message("OPEN test").unwrap();
+        assert_eq!(client.replay_mismatches(), 0);
+        let sent = client.send_move(Move::from_str("D3").unwrap()).unwrap();
+        assert_eq!(sent, Move::from_str("F5").unwrap());
+        assert_eq!(client.replay_mismatches(), 1);
+    }
+}
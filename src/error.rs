@@ -0,0 +1,89 @@
+use std::error;
+use std::fmt;
+use std::io;
+
+use crate::game::board::Board;
+use crate::game::moves::Move;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    ConnectionClosed,
+    MalformedMessage(String),
+    UnknownCommand(String),
+    BadSquare(String),
+    UnexpectedMessage {
+        expected: &'static str,
+    },
+    IllegalMove(Move),
+    // Our board disagrees with what the server sent. `theirs` is the board the
+    // server's view implies, if it can be reconstructed.
+    Desync {
+        reason: String,
+        ours: Board,
+        theirs: Option<Board>,
+    },
+    // A replayed session where this many of our moves differed from the
+    // recorded ones.
+    ReplayMismatch(usize),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    // Whether the error means the connection itself is gone, as opposed to
+    // the peer having sent something we don't understand.
+    pub fn is_connection_lost(&self) -> bool {
+        matches!(self, Error::Io(_) | Error::ConnectionClosed)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+            Error::ConnectionClosed => write!(f, "Connection closed."),
+            Error::MalformedMessage(msg) => write!(f, "Malformed message: {}", msg),
+            Error::UnknownCommand(cmd) => write!(f, "Unknown command: {}", cmd),
+            Error::BadSquare(s) => write!(f, "Bad square: {}", s),
+            Error::UnexpectedMessage { expected } => {
+                write!(f, "Unexpected message: expected {}", expected)
+            }
+            Error::IllegalMove(mv) => write!(f, "Illegal move: {}", mv),
+            Error::Desync {
+                reason,
+                ours,
+                theirs,
+            } => {
+                write!(
+                    f,
+                    "Board desynchronised: {}\nOur board:\n{}",
+                    reason,
+                    ours.diagram()
+                )?;
+                if let Some(theirs) = theirs {
+                    write!(f, "Server's board:\n{}", theirs.diagram())?;
+                }
+                Ok(())
+            }
+            Error::ReplayMismatch(count) => {
+                write!(f, "{} move(s) differ from the recorded session.", count)
+            }
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
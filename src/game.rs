@@ -1,7 +1,17 @@
 pub mod base;
 pub mod board;
+pub mod movegen;
+pub mod moves;
 pub mod opening_db;
+pub mod perft;
+pub mod ponder;
+pub mod position;
+pub mod record;
+pub mod reference;
 pub mod score;
 pub mod square;
+pub mod square_set;
 pub mod strategy;
+pub mod symmetry;
 pub mod util;
+pub mod zobrist;
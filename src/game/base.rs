@@ -1,11 +1,20 @@
 use std::cmp;
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
 
-use crate::cli::Client;
+use crate::cli::{Client, ReconnectPolicy};
+use crate::error::{Error, Result};
 use crate::game::board::Board;
-use crate::game::square::Square;
-use crate::game::strategy::exhausive::WINNABLE_COLOR_HISTORY;
-use crate::game::strategy::{Exhausive, Naive, NegaScout, Strategy};
-use crate::message::{move_message, open_message, pass_message, ServerMessage};
+use crate::game::moves::Move;
+use crate::game::ponder::Ponder;
+use crate::game::square_set::SquareSet;
+use crate::game::strategy::exhausive::SolveMode;
+use crate::game::strategy::info::InfoCallback;
+use crate::game::strategy::{Exhausive, Naive, NegaScout, SearchInfo, Strategy};
+use crate::message::{open_message, ServerMessage};
+use crate::render::BoardView;
+use crate::transport::ReplayTransport;
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum Color {
@@ -22,6 +31,7 @@ impl Color {
     }
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum GameResult {
     Win,
     Lose,
@@ -37,6 +47,9 @@ enum State {
     Wait,
     PlayerTurn,
     OpponentTurn,
+    // reconnected while waiting for the server's reply to our move or for
+    // the opponent's move.
+    Resume,
 }
 
 pub struct Game {
@@ -50,6 +63,14 @@ pub struct Game {
     win_game_count: u16,
     lose_game_count: u16,
     tie_game_count: u16,
+    reconnect_policy: ReconnectPolicy,
+    check_end_counts: bool,
+    pondering: bool,
+    show_board: bool,
+    show_search_info: bool,
+    threads: usize,
+    ponder: Option<Ponder>,
+    pondered_move: Option<Move>,
 }
 
 impl Game {
@@ -57,24 +78,40 @@ impl Game {
 
     fn initialize(client: Client, player: Player, opponent: Player, time: i32) -> Self {
         Self {
-            client: client,
+            client,
             state: State::Wait,
-            player: player,
-            opponent: opponent,
+            player,
+            opponent,
             board: Board::initial(),
-            time: time,
+            time,
             strategy: Box::new(Naive()),
             win_game_count: 0,
             lose_game_count: 0,
             tie_game_count: 0,
+            reconnect_policy: ReconnectPolicy::never(),
+            check_end_counts: false,
+            pondering: false,
+            show_board: false,
+            show_search_info: false,
+            threads: 1,
+            ponder: None,
+            pondered_move: None,
         }
     }
 
-    pub fn launch(host: &str, port: u32, name: &str) -> Result<Self, String> {
-        let mut client = Client::new(host, port);
-        if client.send_message(&open_message(name)).is_err() {
-            return Err("Couldn't start game.".to_string());
-        }
+    pub fn launch(host: &str, port: u32, name: &str) -> Result<Self> {
+        Game::new(Client::new(host, port)?, name)
+    }
+
+    // Plays the recorded session again, comparing our moves with the recorded ones.
+    pub fn replay<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let transport = ReplayTransport::open(path)?;
+        let name = transport.recorded_name().unwrap_or("replay").to_string();
+        Game::new(Client::with_transport(Box::new(transport)), &name)
+    }
+
+    pub fn new(mut client: Client, name: &str) -> Result<Self> {
+        client.send_message(&open_message(name))?;
 
         // initialize players with dummy information.
         let player = Player {
@@ -88,23 +125,85 @@ impl Game {
         Ok(Game::initialize(client, player, opponent, 0))
     }
 
-    pub fn main_loop(mut self) {
+    // Our moves that differed from the recorded ones, when replaying.
+    pub fn replay_mismatches(&self) -> usize {
+        self.client.replay_mismatches()
+    }
+
+    pub fn set_reconnect_policy(&mut self, policy: ReconnectPolicy) {
+        self.reconnect_policy = policy;
+    }
+
+    // Compare the disc counts reported with END against our own board.
+    pub fn set_check_end_counts(&mut self, check: bool) {
+        self.check_end_counts = check;
+    }
+
+    // Search our reply to the predicted opponent move while the opponent thinks.
+    pub fn set_pondering(&mut self, pondering: bool) {
+        self.pondering = pondering;
+    }
+
+    // Draw the board with ANSI colors after every move.
+    pub fn set_show_board(&mut self, show: bool) {
+        self.show_board = show;
+    }
+
+    // Search with this many threads.
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads;
+    }
+
+    // Print depth, score and expected line as our searches go.
+    pub fn set_show_search_info(&mut self, show: bool) {
+        self.show_search_info = show;
+    }
+
+    pub fn main_loop(&mut self) -> Result<()> {
         loop {
-            match self.state {
-                State::Wait => {
-                    let is_bye = self.handle_wait();
-                    if is_bye {
-                        return;
+            let step = match self.state {
+                State::Wait => self.handle_wait(),
+                State::PlayerTurn => self.handle_player_turn().map(|_| false),
+                State::OpponentTurn => self.handle_opponent_turn().map(|_| false),
+                State::Resume => self.handle_resume(),
+            };
+            match step {
+                Ok(true) => return Ok(()),
+                Ok(false) => {}
+                Err(e) if e.is_connection_lost() => self.reconnect(e)?,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn reconnect(&mut self, cause: Error) -> Result<()> {
+        let policy = self.reconnect_policy;
+        for attempt in 1..=policy.max_attempts {
+            println!(
+                "{}. Reconnecting ({}/{})...",
+                cause, attempt, policy.max_attempts
+            );
+            thread::sleep(policy.interval);
+            let name = open_message(&self.player.name);
+            match self
+                .client
+                .reconnect()
+                .and_then(|_| self.client.send_message(&name))
+            {
+                Ok(()) => {
+                    if let State::OpponentTurn = self.state {
+                        self.state = State::Resume;
                     }
+                    return Ok(());
                 }
-                State::PlayerTurn => self.handle_player_turn(),
-                State::OpponentTurn => self.handle_opponent_turn(),
+                Err(e) => println!("Couldn't reconnect: {}", e),
             }
         }
+        Err(cause)
     }
 
-    fn handle_wait(&mut self) -> bool {
-        match self.client.poll_message().unwrap() {
+    fn handle_wait(&mut self) -> Result<bool> {
+        match self.client.poll_message()? {
             ServerMessage::Start {
                 color,
                 op_name,
@@ -112,54 +211,101 @@ impl Game {
             } => {
                 self.reset();
                 self.on_start_message(color, &op_name, remaining_time_ms);
-                false
+                Ok(false)
             }
             ServerMessage::Bye { stat } => {
                 println!("{}", stat);
-                true
+                Ok(true)
             }
-            _ => panic!("Unexpected message: expected Start or Bye"),
+            _ => Err(Error::UnexpectedMessage {
+                expected: "Start or Bye",
+            }),
         }
     }
 
-    fn handle_player_turn(&mut self) {
-        let msg = self.perform_player_move();
+    fn handle_player_turn(&mut self) -> Result<()> {
+        let mv = self.choose_player_move();
+        // the board is only updated once the server has the move, so a move that
+        // couldn't be sent is played again after reconnecting.
+        let mv = self.client.send_move(mv)?;
+        if let Move::Square(square) = mv {
+            self.board = self.board.flip(square.to_uint(), self.player.color);
+        }
         self.state = State::OpponentTurn;
-        self.client.send_message(&msg).unwrap();
-        //self.board.print();
-        match self.client.poll_message().unwrap() {
+        self.print_board(mv, self.opponent.color);
+        match self.client.poll_message()? {
             ServerMessage::Ack { remaining_time_ms } => self.time = remaining_time_ms,
             ServerMessage::End {
                 result,
                 player_count,
                 op_count,
                 reason,
-            } => self.on_end_message(result, player_count, op_count, &reason),
-            _ => panic!("Unexpected message: expected Ack"),
+            } => self.on_end_message(result, player_count, op_count, &reason)?,
+            _ => {
+                return Err(Error::UnexpectedMessage {
+                    expected: "Ack or End",
+                })
+            }
+        };
+        Ok(())
+    }
+
+    fn handle_opponent_turn(&mut self) -> Result<()> {
+        if self.pondering && self.ponder.is_none() {
+            let predicted = Ponder::predict(self.board, self.player.color);
+            let strategy = self.build_strategy(predicted);
+            self.ponder = Some(Ponder::start(predicted, self.player.color, strategy));
+        }
+        match self.client.poll_message()? {
+            ServerMessage::Move { pos } => self.on_move_message(pos)?,
+            ServerMessage::End {
+                result,
+                player_count,
+                op_count,
+                reason,
+            } => self.on_end_message(result, player_count, op_count, &reason)?,
+            _ => {
+                return Err(Error::UnexpectedMessage {
+                    expected: "Move or End",
+                })
+            }
         };
+        Ok(())
     }
 
-    fn handle_opponent_turn(&mut self) {
-        match self.client.poll_message().unwrap() {
-            ServerMessage::Move { pos } => self.on_move_message(pos),
+    fn handle_resume(&mut self) -> Result<bool> {
+        match self.client.poll_message()? {
+            ServerMessage::Start {
+                color,
+                op_name,
+                remaining_time_ms,
+            } => {
+                self.reset();
+                self.on_start_message(color, &op_name, remaining_time_ms);
+            }
+            ServerMessage::Ack { remaining_time_ms } => {
+                self.time = remaining_time_ms;
+                self.state = State::OpponentTurn;
+            }
+            ServerMessage::Move { pos } => self.on_move_message(pos)?,
             ServerMessage::End {
                 result,
                 player_count,
                 op_count,
                 reason,
-            } => self.on_end_message(result, player_count, op_count, &reason),
-            _ => panic!("Unexpected message: expected Move or End"),
+            } => self.on_end_message(result, player_count, op_count, &reason)?,
+            ServerMessage::Bye { stat } => {
+                println!("{}", stat);
+                return Ok(true);
+            }
         };
+        Ok(false)
     }
 
     fn reset(&mut self) {
+        self.stop_pondering();
         self.board = Board::initial();
         self.strategy = Box::new(Naive());
-        // due to memory issue
-        if self.total_game_count() % 5 == 0 {
-            let mut write = WINNABLE_COLOR_HISTORY.write().unwrap();
-            write.clear();
-        }
     }
 
     fn on_start_message(&mut self, color: Color, op_name: &str, time: i32) {
@@ -173,15 +319,45 @@ impl Game {
         };
     }
 
-    fn on_move_message(&mut self, pos: Option<Square>) {
-        if let Some(square) = pos {
-            self.board = self.board.flip(square.to_uint(), self.opponent.color);
+    fn on_move_message(&mut self, pos: Move) -> Result<()> {
+        let color = self.opponent.color;
+        let legal = pos.is_legal(self.board, color);
+        match pos {
+            Move::Square(square) if legal => {
+                self.board = self.board.flip(square.to_uint(), color);
+            }
+            Move::Pass if legal => {}
+            Move::Square(square) => {
+                return Err(Error::Desync {
+                    reason: format!("{:?} played illegal move {}", color, square),
+                    ours: self.board,
+                    theirs: Some(self.board.flip(square.to_uint(), color)),
+                })
+            }
+            Move::Pass => {
+                return Err(Error::Desync {
+                    reason: format!("{:?} passed although it had legal moves", color),
+                    ours: self.board,
+                    theirs: None,
+                })
+            }
+        }
+        if let Some(ponder) = self.ponder.take() {
+            self.pondered_move = ponder.finish(self.board);
         }
-        //self.board.print();
+        self.print_board(pos, self.player.color);
         self.state = State::PlayerTurn;
+        Ok(())
     }
 
-    fn on_end_message(&mut self, result: GameResult, player_count: u8, op_count: u8, reason: &str) {
+    fn on_end_message(
+        &mut self,
+        result: GameResult,
+        player_count: u8,
+        op_count: u8,
+        reason: &str,
+    ) -> Result<()> {
+        self.stop_pondering();
         let result_str = match result {
             GameResult::Win => {
                 self.win_game_count += 1;
@@ -205,38 +381,301 @@ impl Game {
             self.win_game_count, self.lose_game_count, self.tie_game_count
         );
         self.state = State::Wait;
+
+        if !self.check_end_counts {
+            return Ok(());
+        }
+        // the server doesn't send the move that ends the game, so the opponent
+        // may have made one more move than our board shows.
+        let op_color = self.opponent.color;
+        let flippables = self.board.flippable_squares(op_color);
+        let consistent = std::iter::once(self.board)
+            .chain(
+                SquareSet(flippables)
+                    .indices()
+                    .map(|s| self.board.flip(s, op_color)),
+            )
+            .any(|board| {
+                let (player_board, op_board) = board.target_boards(self.player.color);
+                player_board.count_ones() as u8 == player_count
+                    && op_board.count_ones() as u8 == op_count
+            });
+        if !consistent {
+            let (player_board, op_board) = self.board.target_boards(self.player.color);
+            return Err(Error::Desync {
+                reason: format!(
+                    "server reported {}-{} discs, our board has {}-{}",
+                    player_count,
+                    op_count,
+                    player_board.count_ones(),
+                    op_board.count_ones()
+                ),
+                ours: self.board,
+                theirs: None,
+            });
+        }
+        Ok(())
     }
 
-    fn perform_player_move(&mut self) -> String {
-        self.set_strategy();
-        match self.strategy.next_move(self.board, self.player.color) {
-            Some(square) => {
-                self.board = self.board.flip(square.to_uint(), self.player.color);
-                move_message(square)
+    // A bad choice from the strategy or the book is replaced rather than
+    // ending the match.
+    fn choose_player_move(&mut self) -> Move {
+        let mv = match self.pondered_move.take() {
+            Some(mv) => mv,
+            None => {
+                self.set_strategy();
+                self.strategy.next_move(self.board, self.player.color)
             }
-            None => pass_message(),
+        };
+        if mv.is_legal(self.board, self.player.color) {
+            return mv;
         }
+        eprintln!("Illegal move {} chosen, playing a fallback instead.", mv);
+        NegaScout::emergency_move(self.board, self.player.color).map_or(Move::Pass, Move::Square)
     }
 
     fn set_strategy(&mut self) {
-        let count = self.board.empty_squares_count();
-        self.strategy = if count < Game::ENDGAME_BORDER {
-            let time = if count < 20 {
-                self.time as u64 / 3 * 2
+        self.strategy = self.build_strategy(self.board);
+    }
+
+    fn build_strategy(&self, board: Board) -> Box<dyn Strategy + Send + Sync> {
+        let count = board.empty_squares_count();
+        if count < Game::ENDGAME_BORDER {
+            // too deep to count discs in time: settle for a win.
+            let (time, mode) = if count < 20 {
+                (self.time as u64 / 3 * 2, SolveMode::Exact)
             } else {
-                self.time as u64 / 5
+                (self.time as u64 / 5, SolveMode::WinLossDraw)
             };
-            Box::new(Exhausive::new(time))
+            let mut search = Exhausive::new(time);
+            search.set_mode(mode);
+            search.set_threads(self.threads);
+            if let Some(callback) = self.info_callback() {
+                search.set_info_callback(callback);
+            }
+            Box::new(search)
         } else {
-            // need some time to execute exhausive search at the end.
-            Box::new(NegaScout::new(
-                cmp::max((self.time - 30000) / 2, 0) as u64,
-                NegaScout::emergency_move(self.board, self.player.color),
-            ))
+            // need some time to execute exhausive search at the end. The rest
+            // is spread over our moves until then, as iterative deepening uses
+            // up whatever it gets.
+            let moves_left = (count - Game::ENDGAME_BORDER) as i32 / 2 + 1;
+            let mut search = NegaScout::new(
+                cmp::max((self.time - 30000) / moves_left, 0) as u64,
+                NegaScout::emergency_move(board, self.player.color),
+            );
+            search.set_threads(self.threads);
+            if let Some(callback) = self.info_callback() {
+                search.set_info_callback(callback);
+            }
+            Box::new(search)
+        }
+    }
+
+    fn info_callback(&self) -> Option<InfoCallback> {
+        if !self.show_search_info {
+            return None;
+        }
+        Some(Arc::new(|info: &SearchInfo| println!("info {}", info)))
+    }
+
+    fn print_board(&self, last_move: Move, side_to_move: Color) {
+        if !self.show_board {
+            return;
+        }
+        let view = BoardView {
+            board: self.board,
+            side_to_move: Some(side_to_move),
+            last_move: last_move.square(),
         };
+        print!("{}", view.ansi());
+    }
+
+    fn stop_pondering(&mut self) {
+        if let Some(ponder) = self.ponder.take() {
+            ponder.abort();
+        }
+        self.pondered_move = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::Direction;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::time::Duration;
+
+    enum Step {
+        Send(&'static str),
+        Recv,
+    }
+
+    // Serves one connection per script and returns every line read from the client.
+    fn run_against(scripts: Vec<Vec<Step>>, policy: ReconnectPolicy) -> (Result<()>, Vec<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port() as u32;
+        let server = thread::spawn(move || {
+            let mut received = Vec::new();
+            for script in scripts {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut open = String::new();
+                reader.read_line(&mut open).unwrap();
+                received.push(open.trim().to_string());
+                for step in script {
+                    match step {
+                        Step::Send(line) => writeln!(stream, "{}", line).unwrap(),
+                        Step::Recv => {
+                            let mut line = String::new();
+                            reader.read_line(&mut line).unwrap();
+                            received.push(line.trim().to_string());
+                        }
+                    }
+                }
+            }
+            received
+        });
+        let result = Game::launch("127.0.0.1", port, "test").and_then(|mut g| {
+            g.set_reconnect_policy(policy);
+            g.set_check_end_counts(true);
+            g.main_loop()
+        });
+        (result, server.join().unwrap())
+    }
+
+    #[test]
+    fn unknown_command() {
+        match run_against(vec![vec![Step::Send("HELLO")]], ReconnectPolicy::never()).0 {
+            Err(Error::UnknownCommand(cmd)) => assert_eq!(cmd, "HELLO"),
+            _ => panic!("expected UnknownCommand"),
+        }
+    }
+
+    #[test]
+    fn unexpected_message() {
+        match run_against(vec![vec![Step::Send("ACK 1000")]], ReconnectPolicy::never()).0 {
+            Err(Error::UnexpectedMessage { .. }) => {}
+            _ => panic!("expected UnexpectedMessage"),
+        }
+    }
+
+    #[test]
+    fn bad_square() {
+        let script = vec![Step::Send("START WHITE op 1000"), Step::Send("MOVE Z9")];
+        match run_against(vec![script], ReconnectPolicy::never()).0 {
+            Err(Error::BadSquare(s)) => assert_eq!(s, "Z9"),
+            _ => panic!("expected BadSquare"),
+        }
+    }
+
+    #[test]
+    fn connection_closed() {
+        let script = vec![Step::Send("START WHITE op 1000")];
+        match run_against(vec![script], ReconnectPolicy::never()).0 {
+            Err(Error::ConnectionClosed) => {}
+            _ => panic!("expected ConnectionClosed"),
+        }
+    }
+
+    #[test]
+    fn reconnect() {
+        let scripts = vec![
+            vec![
+                Step::Send("START WHITE op 1000"),
+                Step::Send("MOVE F5"),
+                Step::Recv,
+            ],
+            vec![
+                Step::Send("ACK 900"),
+                Step::Send("END TIE 3 3 DOUBLE_PASS"),
+                Step::Send("BYE stat"),
+            ],
+        ];
+        let policy = ReconnectPolicy::new(3, Duration::from_millis(10));
+        let (result, received) = run_against(scripts, policy);
+        result.unwrap();
+        assert_eq!(received[0], "OPEN test");
+        assert!(received[1].starts_with("MOVE "));
+        assert_eq!(received[2], "OPEN test");
+    }
+
+    #[test]
+    fn replay() {
+        let path = std::env::temp_dir().join(format!("reversi_replay_{}.log", std::process::id()));
+        std::fs::write(
+            &path,
+            "0\tS\tOPEN test\n\
+             1\tR\tSTART WHITE op 1000\n\
+             2\tR\tMOVE F5\n\
+             3\tS\tMOVE F6\n\
+             4\tR\tACK 900\n\
+             5\tR\tEND LOSE 2 3 DOUBLE_PASS\n\
+             6\tR\tBYE stat\n",
+        )
+        .unwrap();
+        let result = Game::replay(&path).and_then(|mut g| g.main_loop());
+        std::fs::remove_file(&path).unwrap();
+        result.unwrap();
+    }
+
+    #[test]
+    fn illegal_opponent_move() {
+        let script = vec![Step::Send("START WHITE op 1000"), Step::Send("MOVE A1")];
+        match run_against(vec![script], ReconnectPolicy::never()).0 {
+            Err(Error::Desync { theirs, .. }) => assert!(theirs.is_some()),
+            _ => panic!("expected Desync"),
+        }
+    }
+
+    #[test]
+    fn illegal_opponent_pass() {
+        let script = vec![Step::Send("START WHITE op 1000"), Step::Send("MOVE PASS")];
+        match run_against(vec![script], ReconnectPolicy::never()).0 {
+            Err(Error::Desync { theirs, .. }) => assert!(theirs.is_none()),
+            _ => panic!("expected Desync"),
+        }
+    }
+
+    #[test]
+    fn end_count_mismatch() {
+        let script = vec![
+            Step::Send("START WHITE op 1000"),
+            Step::Send("MOVE F5"),
+            Step::Recv,
+            Step::Send("ACK 900"),
+            Step::Send("END LOSE 2 3 DOUBLE_PASS"),
+        ];
+        match run_against(vec![script], ReconnectPolicy::never()).0 {
+            Err(Error::Desync { .. }) => {}
+            _ => panic!("expected Desync"),
+        }
+    }
+
+    // The server ends the game without sending the opponent's last move, so
+    // the counts are one dark move ahead of our board.
+    #[test]
+    fn end_counts_after_unsent_move() {
+        let script = vec![
+            Step::Send("START WHITE op 1000"),
+            Step::Send("MOVE F5"),
+            Step::Recv,
+            Step::Send("ACK 900"),
+            Step::Send("END LOSE 2 5 DOUBLE_PASS"),
+            Step::Send("BYE stat"),
+        ];
+        run_against(vec![script], ReconnectPolicy::never())
+            .0
+            .unwrap();
     }
 
-    fn total_game_count(&self) -> u16 {
-        self.win_game_count + self.lose_game_count + self.tie_game_count
+    #[test]
+    fn illegal_choice_falls_back() {
+        let transport = ReplayTransport::new(vec![(Direction::Sent, "OPEN test".to_string())]);
+        let mut game = Game::new(Client::with_transport(Box::new(transport)), "test").unwrap();
+        game.pondered_move = Some(Move::Pass);
+        let mv = game.choose_player_move();
+        assert_ne!(mv, Move::Pass);
+        assert!(mv.is_legal(game.board, game.player.color));
     }
 }
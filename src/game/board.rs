@@ -1,196 +1,268 @@
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
 use crate::game::base::Color;
+use crate::game::movegen;
 use crate::game::square::Square;
-use crate::game::util::clz;
+use crate::game::symmetry::Transform;
+use crate::game::zobrist;
 
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+// `key` is the Zobrist key of the position and is kept up to date by every
+// constructor and move.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub struct Board {
-    pub dark: u64,
-    pub light: u64,
+    dark: u64,
+    light: u64,
+    key: u64,
+}
+
+// Everything needed to take back a move made with `Board::make`.
+#[derive(Clone, Copy, Debug)]
+pub struct Undo {
+    square: u8,
+    flipped: u64,
+    color: Color,
+    key: u64,
+}
+
+impl Hash for Board {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.key);
+    }
+}
+
+// A board string is 64 squares from A1 to H8, `X` for dark, `O` for light and
+// `-` for empty, as used by Edax and the FFO test suite.
+impl FromStr for Board {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut dark = 0;
+        let mut light = 0;
+        let mut count = 0;
+        for (i, c) in s.chars().enumerate() {
+            if i >= 64 {
+                return Err("While parsing Board: too many squares.");
+            }
+            match square_color(c) {
+                Some(Some(Color::Dark)) => dark |= 1 << i,
+                Some(Some(Color::Light)) => light |= 1 << i,
+                Some(None) => {}
+                None => return Err("While parsing Board: invalid square."),
+            }
+            count += 1;
+        }
+        if count != 64 {
+            return Err("While parsing Board: too few squares.");
+        }
+        Ok(Self::new(dark, light))
+    }
+}
+
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for i in 0..64 {
+            let c = if self.dark & 1 << i != 0 {
+                'X'
+            } else if self.light & 1 << i != 0 {
+                'O'
+            } else {
+                '-'
+            };
+            write!(f, "{}", c)?;
+        }
+        Ok(())
+    }
+}
+
+// `Some(None)` for an empty square.
+fn square_color(c: char) -> Option<Option<Color>> {
+    match c {
+        'X' | 'x' | '*' => Some(Some(Color::Dark)),
+        'O' | 'o' => Some(Some(Color::Light)),
+        '-' | '.' => Some(None),
+        _ => None,
+    }
 }
 
 impl Board {
-    pub fn initial() -> Self {
+    pub fn new(dark: u64, light: u64) -> Self {
         Self {
-            dark: 1u64 << Square::from_str("D5").unwrap().to_uint()
+            dark,
+            light,
+            key: zobrist::compute_key(dark, light),
+        }
+    }
+
+    pub fn initial() -> Self {
+        Self::new(
+            1u64 << Square::from_str("D5").unwrap().to_uint()
                 | 1u64 << Square::from_str("E4").unwrap().to_uint(),
-            light: 1u64 << Square::from_str("D4").unwrap().to_uint()
+            1u64 << Square::from_str("D4").unwrap().to_uint()
                 | 1u64 << Square::from_str("E5").unwrap().to_uint(),
-        }
+        )
+    }
+
+    #[inline]
+    pub fn dark(&self) -> u64 {
+        self.dark
+    }
+
+    #[inline]
+    pub fn light(&self) -> u64 {
+        self.light
+    }
+
+    #[inline]
+    pub fn key(&self) -> u64 {
+        self.key
     }
 
-    #[allow(dead_code)]
     pub fn print(&self) {
-        println!(" |A B C D E F G H");
-        println!("-+---------------");
+        print!("{}", self.diagram());
+    }
+
+    // A board string followed by the side to move, e.g.
+    // `---------------------------OX------XO--------------------------- X`.
+    // Anything after the side to move (FFO style `;` comments) is ignored.
+    pub fn parse_obf(s: &str) -> Result<(Self, Color), &'static str> {
+        let mut split = s.split_whitespace();
+        let board = Self::from_str(split.next().unwrap_or(""))?;
+        let color = match split.next().map(|s| s.trim_end_matches(';')) {
+            Some("X") | Some("x") | Some("*") | Some("B") | Some("b") => Color::Dark,
+            Some("O") | Some("o") | Some("W") | Some("w") => Color::Light,
+            _ => return Err("While parsing Board: invalid side to move."),
+        };
+        Ok((board, color))
+    }
+
+    pub fn to_obf(&self, color: Color) -> String {
+        let side = match color {
+            Color::Dark => 'X',
+            Color::Light => 'O',
+        };
+        format!("{} {}", self, side)
+    }
+
+    // Reads the output of `diagram` back, as well as plain diagrams with one
+    // row of eight squares per line, optionally between row numbers. Lines
+    // that aren't rows (column labels, rulers) are skipped.
+    pub fn from_diagram(s: &str) -> Result<Self, &'static str> {
+        let mut squares = String::new();
+        let mut rows = 0;
+        for line in s.lines() {
+            let row: Vec<char> = match line.find('|') {
+                Some(i) => {
+                    if line[..i].trim().parse::<u8>().is_err() {
+                        continue;
+                    }
+                    // one square every two columns, trailing blanks trimmed.
+                    let mut body: Vec<char> = line[i + 1..].chars().collect();
+                    body.resize(16, ' ');
+                    body.iter()
+                        .step_by(2)
+                        .map(|&c| if c == ' ' { '-' } else { c })
+                        .collect()
+                }
+                None => line
+                    .chars()
+                    .filter(|c| !c.is_whitespace())
+                    .collect::<String>()
+                    .trim_matches(|c: char| c.is_ascii_digit())
+                    .chars()
+                    .collect(),
+            };
+            if row.len() != 8 || row.iter().any(|&c| square_color(c).is_none()) {
+                continue;
+            }
+            rows += 1;
+            squares.extend(row);
+        }
+        if rows != 8 {
+            return Err("While parsing Board: a diagram needs eight rows.");
+        }
+        Self::from_str(&squares)
+    }
+
+    pub fn diagram(&self) -> String {
+        let mut ret = String::from(" |A B C D E F G H\n-+---------------\n");
         for i in 0..64 {
             if i % 8 == 0 {
                 if i > 0 {
-                    print!("\n");
+                    ret.push('\n');
                 }
-                print!("{}|", i / 8 + 1);
+                ret.push_str(&format!("{}|", i / 8 + 1));
             }
 
             if self.dark & 1 << i != 0 {
-                print!("x ");
+                ret.push_str("x ");
             } else if self.light & 1 << i != 0 {
-                print!("o ");
+                ret.push_str("o ");
             } else {
-                print!("  ");
+                ret.push_str("  ");
             }
         }
-        print!("\n");
+        ret.push('\n');
+        ret
     }
 
     #[inline]
     pub fn flip(&self, square: u8, color: Color) -> Self {
+        let mut ret = *self;
+        ret.make(square, color);
+        ret
+    }
+
+    // Plays `square` in place, returning what `unmake` needs to undo it.
+    #[inline]
+    pub fn make(&mut self, square: u8, color: Color) -> Undo {
         let flipped = self.flipped_squares(square, color);
+        let undo = Undo {
+            square,
+            flipped,
+            color,
+            key: self.key,
+        };
         match color {
-            Color::Dark => Self {
-                dark: self.dark | 1u64 << square | flipped,
-                light: self.light & !flipped,
-            },
-            Color::Light => Self {
-                dark: self.dark & !flipped,
-                light: self.light | 1u64 << square | flipped,
-            },
+            Color::Dark => {
+                self.dark |= 1u64 << square | flipped;
+                self.light &= !flipped;
+            }
+            Color::Light => {
+                self.dark &= !flipped;
+                self.light |= 1u64 << square | flipped;
+            }
         }
+        self.key ^= zobrist::square_key(square, color) ^ zobrist::flip_key(flipped);
+        undo
+    }
+
+    #[inline]
+    pub fn unmake(&mut self, undo: Undo) {
+        match undo.color {
+            Color::Dark => {
+                self.dark &= !(1u64 << undo.square | undo.flipped);
+                self.light |= undo.flipped;
+            }
+            Color::Light => {
+                self.dark |= undo.flipped;
+                self.light &= !(1u64 << undo.square | undo.flipped);
+            }
+        }
+        self.key = undo.key;
     }
 
     #[inline]
     pub fn flippable_squares(&self, color: Color) -> u64 {
         let (target_board, other_board) = self.target_boards(color);
-
-        let horizontal_watcher = other_board & 0x7e7e7e7e7e7e7e7e;
-        let vertical_watcher = other_board & 0x00ffffffffffff00;
-        let sides_watcher = other_board & 0x007e7e7e7e7e7e00;
-        let blank_squares = !(target_board | other_board);
-
-        // one can flip atmost 6 disks.
-        // opening for loops for speed up
-
-        // west
-        let mut tmp = horizontal_watcher & (target_board << 1);
-        tmp |= horizontal_watcher & tmp << 1;
-        tmp |= horizontal_watcher & tmp << 1;
-        tmp |= horizontal_watcher & tmp << 1;
-        tmp |= horizontal_watcher & tmp << 1;
-        tmp |= horizontal_watcher & tmp << 1;
-        let legal_west = blank_squares & tmp << 1;
-
-        // east
-        let mut tmp = horizontal_watcher & target_board >> 1;
-        tmp |= horizontal_watcher & tmp >> 1;
-        tmp |= horizontal_watcher & tmp >> 1;
-        tmp |= horizontal_watcher & tmp >> 1;
-        tmp |= horizontal_watcher & tmp >> 1;
-        tmp |= horizontal_watcher & tmp >> 1;
-        let legal_east = blank_squares & tmp >> 1;
-
-        // top
-        let mut tmp = vertical_watcher & target_board << 8;
-        tmp |= vertical_watcher & tmp << 8;
-        tmp |= vertical_watcher & tmp << 8;
-        tmp |= vertical_watcher & tmp << 8;
-        tmp |= vertical_watcher & tmp << 8;
-        tmp |= vertical_watcher & tmp << 8;
-        let legal_north = blank_squares & tmp << 8;
-
-        // bottom
-        let mut tmp = vertical_watcher & target_board >> 8;
-        tmp |= vertical_watcher & tmp >> 8;
-        tmp |= vertical_watcher & tmp >> 8;
-        tmp |= vertical_watcher & tmp >> 8;
-        tmp |= vertical_watcher & tmp >> 8;
-        tmp |= vertical_watcher & tmp >> 8;
-        let legal_south = blank_squares & tmp >> 8;
-
-        // north west
-        let mut tmp = sides_watcher & target_board << 9;
-        tmp |= sides_watcher & tmp << 9;
-        tmp |= sides_watcher & tmp << 9;
-        tmp |= sides_watcher & tmp << 9;
-        tmp |= sides_watcher & tmp << 9;
-        tmp |= sides_watcher & tmp << 9;
-        let legal_north_west = blank_squares & tmp << 9;
-
-        // north east
-        let mut tmp = sides_watcher & target_board << 7;
-        tmp |= sides_watcher & tmp << 7;
-        tmp |= sides_watcher & tmp << 7;
-        tmp |= sides_watcher & tmp << 7;
-        tmp |= sides_watcher & tmp << 7;
-        tmp |= sides_watcher & tmp << 7;
-        let legal_north_east = blank_squares & tmp << 7;
-
-        // south west
-        let mut tmp = sides_watcher & target_board >> 7;
-        tmp |= sides_watcher & tmp >> 7;
-        tmp |= sides_watcher & tmp >> 7;
-        tmp |= sides_watcher & tmp >> 7;
-        tmp |= sides_watcher & tmp >> 7;
-        tmp |= sides_watcher & tmp >> 7;
-        let legal_south_west = blank_squares & tmp >> 7;
-
-        // south east
-        let mut tmp = sides_watcher & target_board >> 9;
-        tmp |= sides_watcher & tmp >> 9;
-        tmp |= sides_watcher & tmp >> 9;
-        tmp |= sides_watcher & tmp >> 9;
-        tmp |= sides_watcher & tmp >> 9;
-        tmp |= sides_watcher & tmp >> 9;
-        let legal_south_east = blank_squares & tmp >> 9;
-
-        legal_west
-            | legal_east
-            | legal_north
-            | legal_south
-            | legal_north_west
-            | legal_north_east
-            | legal_south_west
-            | legal_south_east
+        movegen::mobility(target_board, other_board)
     }
 
     #[inline]
     pub fn flipped_squares(&self, square_uint: u8, color: Color) -> u64 {
-        let (target_board, mut other_board) = self.target_boards(color);
-
-        let mut ret = 0u64;
-
-        let mut mask = 0x0080808080808080u64 >> (63 - square_uint);
-        let mut outflank = 0x8000000000000000u64 >> clz(!other_board & mask) & target_board;
-        let mut flipped = (-(outflank as i128) * 2) as u64 & mask;
-        mask = 0x0101010101010100u64 << square_uint;
-        outflank = mask & ((other_board | !mask) as u128 + 1) as u64 & target_board;
-        flipped |= (outflank as i128 - ((outflank != 0) as i128)) as u64 & mask;
-        ret |= flipped;
-
-        other_board &= 0x7e7e7e7e7e7e7e7eu64;
-
-        let mut mask = 0x7f00000000000000u64 >> (63 - square_uint);
-        let mut outflank = 0x8000000000000000u64 >> clz(!other_board & mask) & target_board;
-        let mut flipped = (-(outflank as i128) * 2) as u64 & mask;
-        mask = 0x00000000000000feu64 << square_uint;
-        outflank = mask & ((other_board | !mask) as u128 + 1) as u64 & target_board;
-        flipped |= (outflank as i128 - ((outflank != 0) as i128)) as u64 & mask;
-        ret |= flipped;
-
-        let mut mask = 0x0102040810204000u64 >> (63 - square_uint);
-        let mut outflank = 0x8000000000000000u64 >> clz(!other_board & mask) & target_board;
-        let mut flipped = (-(outflank as i128) * 2) as u64 & mask;
-        mask = 0x0002040810204080u64 << square_uint;
-        outflank = mask & ((other_board | !mask) as u128 + 1) as u64 & target_board;
-        flipped |= (outflank as i128 - ((outflank != 0) as i128)) as u64 & mask;
-        ret |= flipped;
-
-        let mut mask = 0x0040201008040201u64 >> (63 - square_uint);
-        let mut outflank = 0x8000000000000000u64 >> clz(!other_board & mask) & target_board;
-        let mut flipped = (-(outflank as i128) * 2) as u64 & mask;
-        mask = 0x8040201008040200u64 << square_uint;
-        outflank = mask & ((other_board | !mask) as u128 + 1) as u64 & target_board;
-        flipped |= (outflank as i128 - ((outflank != 0) as i128)) as u64 & mask;
-        ret |= flipped;
-
-        ret
+        let (target_board, other_board) = self.target_boards(color);
+        movegen::flips(target_board, other_board, square_uint)
     }
 
     #[inline]
@@ -235,7 +307,7 @@ impl Board {
         dark |= 0x0055005500550055 & (tmp >> 8);
         dark |= 0x5500550055005500 & (tmp >> 1);
 
-        Self { light, dark }
+        Self::new(dark, light)
     }
 
     pub fn rotate_180(&self) -> Self {
@@ -250,16 +322,30 @@ impl Board {
         let mut tmp = self.dark;
         tmp = ((tmp >> 8) & 0x00ff00ff00ff00ff) | ((tmp & 0x00ff00ff00ff00ff) << 8);
         tmp = ((tmp >> 16) & 0x0000ffff0000ffff) | ((tmp & 0x0000ffff0000ffff) << 16);
-        tmp = (tmp >> 32) | (tmp << 32);
+        tmp = tmp.rotate_left(32);
         let dark = tmp;
 
         let mut tmp = self.light;
         tmp = ((tmp >> 8) & 0x00ff00ff00ff00ff) | ((tmp & 0x00ff00ff00ff00ff) << 8);
         tmp = ((tmp >> 16) & 0x0000ffff0000ffff) | ((tmp & 0x0000ffff0000ffff) << 16);
-        tmp = (tmp >> 32) | (tmp << 32);
+        tmp = tmp.rotate_left(32);
         let light = tmp;
 
-        Self { dark, light }
+        Self::new(dark, light)
+    }
+
+    pub fn transform(&self, t: Transform) -> Self {
+        Self::new(t.apply_bits(self.dark), t.apply_bits(self.light))
+    }
+
+    // The smallest of the eight symmetric images, along with the transform
+    // that maps this board onto it.
+    pub fn canonical(&self) -> (Self, Transform) {
+        Transform::ALL
+            .iter()
+            .map(|&t| (self.transform(t), t))
+            .min_by_key(|(b, _)| (b.dark, b.light))
+            .unwrap()
     }
 
     #[inline]
@@ -298,14 +384,73 @@ mod tests {
 
     #[test]
     fn mirror() {
-        let board = Board {
-            dark: 0x7844444870504844,
-            light: 0x0,
-        };
-        let expected = Board {
-            dark: 0x4448507048444478,
-            light: 0x0,
-        };
+        let board = Board::new(0x7844444870504844, 0x0);
+        let expected = Board::new(0x4448507048444478, 0x0);
         assert_eq!(board.mirror(), expected);
     }
+
+    #[test]
+    fn obf() {
+        let s = "---------------------------OX------XO--------------------------- X";
+        let (board, color) = Board::parse_obf(s).unwrap();
+        assert_eq!(board, Board::initial());
+        assert_eq!(color, Color::Dark);
+        assert_eq!(board.to_obf(color), s);
+
+        let board = Board::new(0x6000100810120500, 0x8efceff76f6d3a3f);
+        let s = format!("{}; comment", board.to_obf(Color::Light));
+        assert_eq!(Board::parse_obf(&s).unwrap(), (board, Color::Light));
+
+        assert!(Board::from_str("XO").is_err());
+        assert!(Board::from_str(&"?".repeat(64)).is_err());
+        assert!(Board::parse_obf(&"-".repeat(64)).is_err());
+    }
+
+    #[test]
+    fn from_diagram() {
+        let board = Board::new(0x6000100810120500, 0x8efceff76f6d3a3f);
+        assert_eq!(Board::from_diagram(&board.diagram()).unwrap(), board);
+
+        let plain = "  A B C D E F G H
+                     1 - - - - - - - - 1
+                     2 - - - - - - - - 2
+                     3 - - - - - - - - 3
+                     4 - - - O X - - - 4
+                     5 - - - X O - - - 5
+                     6 - - - - - - - - 6
+                     7 - - - - - - - - 7
+                     8 - - - - - - - - 8
+                       A B C D E F G H";
+        assert_eq!(Board::from_diagram(plain).unwrap(), Board::initial());
+        assert!(Board::from_diagram("--------\n").is_err());
+    }
+
+    #[test]
+    fn make_unmake() {
+        let mut board = Board::initial();
+        let mut history = Vec::new();
+        let mut color = Color::Dark;
+        // play the first legal square until neither side can move
+        loop {
+            let flippables = board.flippable_squares(color);
+            if flippables == 0 {
+                if board.flippable_squares(color.opposite()) == 0 {
+                    break;
+                }
+                color = color.opposite();
+                continue;
+            }
+            let before = board;
+            let undo = board.make(flippables.trailing_zeros() as u8, color);
+            assert_eq!(board.key(), Board::new(board.dark, board.light).key());
+            history.push((before, undo));
+            color = color.opposite();
+        }
+        for (before, undo) in history.into_iter().rev() {
+            board.unmake(undo);
+            assert_eq!(board, before);
+            assert_eq!(board.key(), before.key());
+        }
+        assert_eq!(board, Board::initial());
+    }
 }
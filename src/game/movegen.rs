@@ -0,0 +1,71 @@
+// Move generation backends. The scalar code runs everywhere; on x86_64 the
+// AVX2 mobility is picked at runtime when the CPU has it. Flips stay scalar:
+// the inlined shifts beat PEXT/PDEP table lookups by a wide margin.
+
+#[cfg(target_arch = "x86_64")]
+mod avx2;
+pub mod scalar;
+
+// Squares `target_board` can play on.
+#[inline]
+pub fn mobility(target_board: u64, other_board: u64) -> u64 {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if avx2::usable() {
+            return unsafe { avx2::mobility(target_board, other_board) };
+        }
+    }
+    scalar::mobility(target_board, other_board)
+}
+
+// Discs of `other_board` turned over by `target_board` playing `square`.
+#[inline]
+pub fn flips(target_board: u64, other_board: u64, square: u8) -> u64 {
+    scalar::flips(target_board, other_board, square)
+}
+
+// Name of the backend `mobility` dispatches to on this CPU.
+pub fn mobility_backend() -> &'static str {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if avx2::usable() {
+            return "avx2";
+        }
+    }
+    "scalar"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::util::XorShift;
+
+    // Random boards with disjoint discs, denser or sparser depending on the
+    // round so that long flipping lines show up too.
+    fn random_boards(count: usize) -> Vec<(u64, u64)> {
+        let mut rng = XorShift::new(0x9e37_79b9_7f4a_7c15);
+        (0..count)
+            .map(|i| {
+                let occupied = match i % 3 {
+                    0 => rng.next_u64() | rng.next_u64(),
+                    1 => rng.next_u64(),
+                    _ => rng.next_u64() & rng.next_u64(),
+                };
+                let dark = occupied & rng.next_u64();
+                (dark, occupied & !dark)
+            })
+            .collect()
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn avx2_matches_scalar() {
+        if !is_x86_feature_detected!("avx2") {
+            return;
+        }
+        for (p, o) in random_boards(10000) {
+            assert_eq!(unsafe { avx2::mobility(p, o) }, scalar::mobility(p, o));
+            assert_eq!(unsafe { avx2::mobility(o, p) }, scalar::mobility(o, p));
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use lazy_static::lazy_static;
+use std::arch::x86_64::*;
+
+lazy_static! {
+    static ref USABLE: bool = is_x86_feature_detected!("avx2");
+}
+
+// Whether the CPU has AVX2, checked once.
+pub fn usable() -> bool {
+    *USABLE
+}
+
+// The scalar mobility with the four shift amounts (west/east, north/south and
+// both diagonals) handled in parallel, one per 64 bit lane.
+#[target_feature(enable = "avx2")]
+pub unsafe fn mobility(target_board: u64, other_board: u64) -> u64 {
+    let shifts = _mm256_set_epi64x(7, 9, 8, 1);
+    let watchers = _mm256_and_si256(
+        _mm256_set1_epi64x(other_board as i64),
+        _mm256_set_epi64x(
+            0x007e7e7e7e7e7e00,
+            0x007e7e7e7e7e7e00,
+            0x00ffffffffffff00,
+            0x7e7e7e7e7e7e7e7e,
+        ),
+    );
+    let target = _mm256_set1_epi64x(target_board as i64);
+
+    // one can flip atmost 6 disks.
+    let mut left = _mm256_and_si256(watchers, _mm256_sllv_epi64(target, shifts));
+    let mut right = _mm256_and_si256(watchers, _mm256_srlv_epi64(target, shifts));
+    for _ in 0..5 {
+        left = _mm256_or_si256(
+            left,
+            _mm256_and_si256(watchers, _mm256_sllv_epi64(left, shifts)),
+        );
+        right = _mm256_or_si256(
+            right,
+            _mm256_and_si256(watchers, _mm256_srlv_epi64(right, shifts)),
+        );
+    }
+    let legal = _mm256_or_si256(
+        _mm256_sllv_epi64(left, shifts),
+        _mm256_srlv_epi64(right, shifts),
+    );
+
+    let legal = _mm_or_si128(
+        _mm256_castsi256_si128(legal),
+        _mm256_extracti128_si256(legal, 1),
+    );
+    let legal = _mm_or_si128(legal, _mm_unpackhi_epi64(legal, legal));
+    _mm_cvtsi128_si64(legal) as u64 & !(target_board | other_board)
+}
@@ -0,0 +1,134 @@
+use crate::game::util::clz;
+
+#[inline]
+pub fn mobility(target_board: u64, other_board: u64) -> u64 {
+    let horizontal_watcher = other_board & 0x7e7e7e7e7e7e7e7e;
+    let vertical_watcher = other_board & 0x00ffffffffffff00;
+    let sides_watcher = other_board & 0x007e7e7e7e7e7e00;
+    let blank_squares = !(target_board | other_board);
+
+    // one can flip atmost 6 disks.
+    // opening for loops for speed up
+
+    // west
+    let mut tmp = horizontal_watcher & (target_board << 1);
+    tmp |= horizontal_watcher & tmp << 1;
+    tmp |= horizontal_watcher & tmp << 1;
+    tmp |= horizontal_watcher & tmp << 1;
+    tmp |= horizontal_watcher & tmp << 1;
+    tmp |= horizontal_watcher & tmp << 1;
+    let legal_west = blank_squares & tmp << 1;
+
+    // east
+    let mut tmp = horizontal_watcher & target_board >> 1;
+    tmp |= horizontal_watcher & tmp >> 1;
+    tmp |= horizontal_watcher & tmp >> 1;
+    tmp |= horizontal_watcher & tmp >> 1;
+    tmp |= horizontal_watcher & tmp >> 1;
+    tmp |= horizontal_watcher & tmp >> 1;
+    let legal_east = blank_squares & tmp >> 1;
+
+    // top
+    let mut tmp = vertical_watcher & target_board << 8;
+    tmp |= vertical_watcher & tmp << 8;
+    tmp |= vertical_watcher & tmp << 8;
+    tmp |= vertical_watcher & tmp << 8;
+    tmp |= vertical_watcher & tmp << 8;
+    tmp |= vertical_watcher & tmp << 8;
+    let legal_north = blank_squares & tmp << 8;
+
+    // bottom
+    let mut tmp = vertical_watcher & target_board >> 8;
+    tmp |= vertical_watcher & tmp >> 8;
+    tmp |= vertical_watcher & tmp >> 8;
+    tmp |= vertical_watcher & tmp >> 8;
+    tmp |= vertical_watcher & tmp >> 8;
+    tmp |= vertical_watcher & tmp >> 8;
+    let legal_south = blank_squares & tmp >> 8;
+
+    // north west
+    let mut tmp = sides_watcher & target_board << 9;
+    tmp |= sides_watcher & tmp << 9;
+    tmp |= sides_watcher & tmp << 9;
+    tmp |= sides_watcher & tmp << 9;
+    tmp |= sides_watcher & tmp << 9;
+    tmp |= sides_watcher & tmp << 9;
+    let legal_north_west = blank_squares & tmp << 9;
+
+    // north east
+    let mut tmp = sides_watcher & target_board << 7;
+    tmp |= sides_watcher & tmp << 7;
+    tmp |= sides_watcher & tmp << 7;
+    tmp |= sides_watcher & tmp << 7;
+    tmp |= sides_watcher & tmp << 7;
+    tmp |= sides_watcher & tmp << 7;
+    let legal_north_east = blank_squares & tmp << 7;
+
+    // south west
+    let mut tmp = sides_watcher & target_board >> 7;
+    tmp |= sides_watcher & tmp >> 7;
+    tmp |= sides_watcher & tmp >> 7;
+    tmp |= sides_watcher & tmp >> 7;
+    tmp |= sides_watcher & tmp >> 7;
+    tmp |= sides_watcher & tmp >> 7;
+    let legal_south_west = blank_squares & tmp >> 7;
+
+    // south east
+    let mut tmp = sides_watcher & target_board >> 9;
+    tmp |= sides_watcher & tmp >> 9;
+    tmp |= sides_watcher & tmp >> 9;
+    tmp |= sides_watcher & tmp >> 9;
+    tmp |= sides_watcher & tmp >> 9;
+    tmp |= sides_watcher & tmp >> 9;
+    let legal_south_east = blank_squares & tmp >> 9;
+
+    legal_west
+        | legal_east
+        | legal_north
+        | legal_south
+        | legal_north_west
+        | legal_north_east
+        | legal_south_west
+        | legal_south_east
+}
+
+#[inline]
+pub fn flips(target_board: u64, mut other_board: u64, square_uint: u8) -> u64 {
+    let mut ret = 0u64;
+
+    let mut mask = 0x0080808080808080u64 >> (63 - square_uint);
+    let mut outflank = 0x8000000000000000u64 >> clz(!other_board & mask) & target_board;
+    let mut flipped = (-(outflank as i128) * 2) as u64 & mask;
+    mask = 0x0101010101010100u64 << square_uint;
+    outflank = mask & ((other_board | !mask) as u128 + 1) as u64 & target_board;
+    flipped |= (outflank as i128 - ((outflank != 0) as i128)) as u64 & mask;
+    ret |= flipped;
+
+    other_board &= 0x7e7e7e7e7e7e7e7eu64;
+
+    let mut mask = 0x7f00000000000000u64 >> (63 - square_uint);
+    let mut outflank = 0x8000000000000000u64 >> clz(!other_board & mask) & target_board;
+    let mut flipped = (-(outflank as i128) * 2) as u64 & mask;
+    mask = 0x00000000000000feu64 << square_uint;
+    outflank = mask & ((other_board | !mask) as u128 + 1) as u64 & target_board;
+    flipped |= (outflank as i128 - ((outflank != 0) as i128)) as u64 & mask;
+    ret |= flipped;
+
+    let mut mask = 0x0102040810204000u64 >> (63 - square_uint);
+    let mut outflank = 0x8000000000000000u64 >> clz(!other_board & mask) & target_board;
+    let mut flipped = (-(outflank as i128) * 2) as u64 & mask;
+    mask = 0x0002040810204080u64 << square_uint;
+    outflank = mask & ((other_board | !mask) as u128 + 1) as u64 & target_board;
+    flipped |= (outflank as i128 - ((outflank != 0) as i128)) as u64 & mask;
+    ret |= flipped;
+
+    let mut mask = 0x0040201008040201u64 >> (63 - square_uint);
+    let mut outflank = 0x8000000000000000u64 >> clz(!other_board & mask) & target_board;
+    let mut flipped = (-(outflank as i128) * 2) as u64 & mask;
+    mask = 0x8040201008040200u64 << square_uint;
+    outflank = mask & ((other_board | !mask) as u128 + 1) as u64 & target_board;
+    flipped |= (outflank as i128 - ((outflank != 0) as i128)) as u64 & mask;
+    ret |= flipped;
+
+    ret
+}
@@ -0,0 +1,80 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::game::base::Color;
+use crate::game::board::Board;
+use crate::game::square::Square;
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Move {
+    Square(Square),
+    Pass,
+}
+
+impl Move {
+    pub fn square(self) -> Option<Square> {
+        match self {
+            Move::Square(square) => Some(square),
+            Move::Pass => None,
+        }
+    }
+
+    // A pass is only legal when there is no square to play.
+    pub fn is_legal(self, board: Board, color: Color) -> bool {
+        let flippables = board.flippable_squares(color);
+        match self {
+            Move::Square(square) => flippables & 1 << square.to_uint() != 0,
+            Move::Pass => flippables == 0,
+        }
+    }
+}
+
+impl From<Square> for Move {
+    fn from(square: Square) -> Self {
+        Move::Square(square)
+    }
+}
+
+impl FromStr for Move {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("PASS") {
+            Ok(Move::Pass)
+        } else {
+            Square::from_str(s).map(Move::Square)
+        }
+    }
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Move::Square(square) => write!(f, "{}", square),
+            Move::Pass => write!(f, "PASS"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(Move::from_str("PASS"), Ok(Move::Pass));
+        assert_eq!(Move::from_str("pass"), Ok(Move::Pass));
+        let mv = Move::from_str("f5").unwrap();
+        assert_eq!(mv, Move::Square(Square::from_str("F5").unwrap()));
+        assert_eq!(mv.to_string(), "F5");
+        assert!(Move::from_str("PA").is_err());
+    }
+
+    #[test]
+    fn is_legal() {
+        let board = Board::initial();
+        assert!(Move::from_str("F5").unwrap().is_legal(board, Color::Dark));
+        assert!(!Move::from_str("F5").unwrap().is_legal(board, Color::Light));
+        assert!(!Move::Pass.is_legal(board, Color::Dark));
+    }
+}
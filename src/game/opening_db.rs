@@ -7,11 +7,14 @@ use tar::Archive;
 
 use crate::game::base::Color;
 use crate::game::board::Board;
+use crate::game::moves::Move;
+use crate::game::record::GameRecord;
 use crate::game::square::Square;
+use crate::game::zobrist::BoardMap;
 
 lazy_static! {
-    pub static ref DARK_MOVES: RwLock<HashMap<Board, Square>> = RwLock::new(HashMap::new());
-    pub static ref LIGHT_MOVES: RwLock<HashMap<Board, Square>> = RwLock::new(HashMap::new());
+    pub static ref DARK_MOVES: RwLock<BoardMap<Square>> = RwLock::new(BoardMap::default());
+    pub static ref LIGHT_MOVES: RwLock<BoardMap<Square>> = RwLock::new(BoardMap::default());
 }
 
 pub fn load_from_file() {
@@ -21,122 +24,63 @@ pub fn load_from_file() {
     ar.unpack(".").unwrap();
     println!("Unpacked tar");
 
-    let mut dark_moves_count: HashMap<Board, HashMap<u8, i32>> = HashMap::new();
-    let mut light_moves_count: HashMap<Board, HashMap<u8, i32>> = HashMap::new();
+    let mut dark_moves_count: BoardMap<HashMap<u8, i32>> = BoardMap::default();
+    let mut light_moves_count: BoardMap<HashMap<u8, i32>> = BoardMap::default();
 
     let contents = fs::read_to_string("bin/opening_book.gam").unwrap();
     for line in contents.lines() {
-        let mut board = Board::initial();
+        let mut record = GameRecord::new();
         let mut line = line.to_string();
         let winner = line.pop().unwrap();
-        let target_count = {
+        let (winner, target_count) = {
             if winner == '+' {
-                &mut dark_moves_count
+                (Color::Dark, &mut dark_moves_count)
             } else if winner == '-' {
-                &mut light_moves_count
+                (Color::Light, &mut light_moves_count)
             } else {
                 panic!("invalid");
             }
         };
-        for (i, c) in line.chars().enumerate() {
-            let color = {
-                if i % 2 == 0 {
-                    Color::Dark
-                } else {
-                    Color::Light
-                }
-            };
+        for c in line.chars() {
             let square = c as u8 - 33;
-            if (winner == '+' && i % 2 == 0) || (winner == '-' && i % 2 == 1) {
-                target_count
-                    .entry(board)
-                    .and_modify(|hm| {
-                        let _ = *hm.entry(square).and_modify(|c| *c += 1).or_insert(1);
-                    })
-                    .or_insert_with(|| {
-                        let mut hm = HashMap::new();
-                        hm.insert(square, 1);
-                        hm
-                    });
+            record
+                .play(Move::Square(Square::from_uint(square)))
+                .unwrap();
+            // the move just played, after any pass it needed.
+            let position = record.position_at(record.ply() - 1).unwrap();
+            if position.color == winner {
+                *target_count
+                    .entry(position.board)
+                    .or_insert_with(HashMap::new)
+                    .entry(square)
+                    .or_insert(0) += 1;
             }
-            board = board.flip(square, color);
         }
     }
 
-    dark_moves_count.iter().for_each(|(&b, hm)| {
-        let square: u8 = *hm
-            .iter()
-            .fold((None, 0), |(cur_s, cur_c), (s, c)| {
-                if cur_c < *c {
-                    (Some(s), *c)
-                } else {
-                    (cur_s, cur_c)
-                }
-            })
-            .0
-            .unwrap();
-        let square = Square::from_uint(square);
-        for (board, s) in [
-            (b, square),
-            (b.rotate_90(), square.rotate_90()),
-            (b.rotate_180(), square.rotate_180()),
-            (b.rotate_270(), square.rotate_270()),
-        ]
-        .iter()
-        {
-            DARK_MOVES.write().unwrap().insert(*board, *s);
-        }
-
-        let b = b.mirror();
-        let square = square.mirror();
-        for (board, s) in [
-            (b, square),
-            (b.rotate_90(), square.rotate_90()),
-            (b.rotate_180(), square.rotate_180()),
-            (b.rotate_270(), square.rotate_270()),
-        ]
-        .iter()
-        {
-            DARK_MOVES.write().unwrap().insert(*board, *s);
-        }
-    });
+    store_most_played(&dark_moves_count, &DARK_MOVES);
+    store_most_played(&light_moves_count, &LIGHT_MOVES);
+    fs::remove_file("bin/opening_book.gam").unwrap();
+}
 
-    light_moves_count.iter().for_each(|(&b, hm)| {
-        let square: u8 = *hm
-            .iter()
-            .fold((None, 0), |(cur_s, cur_c), (s, c)| {
-                if cur_c < *c {
-                    (Some(s), *c)
-                } else {
-                    (cur_s, cur_c)
-                }
-            })
-            .0
-            .unwrap();
-        let square = Square::from_uint(square);
-        for (board, s) in [
-            (b, square),
-            (b.rotate_90(), square.rotate_90()),
-            (b.rotate_180(), square.rotate_180()),
-            (b.rotate_270(), square.rotate_270()),
-        ]
-        .iter()
-        {
-            LIGHT_MOVES.write().unwrap().insert(*board, *s);
-        }
+// Only the canonical form of each position is stored; lookups go through
+// `book_move`.
+fn store_most_played(counts: &BoardMap<HashMap<u8, i32>>, moves: &RwLock<BoardMap<Square>>) {
+    let mut write = moves.write().unwrap();
+    for (b, hm) in counts.iter() {
+        let square = *hm.iter().max_by_key(|&(_, c)| *c).unwrap().0;
+        let (canonical, t) = b.canonical();
+        write.insert(canonical, Square::from_uint(square).transform(t));
+    }
+}
 
-        let b = b.mirror();
-        let square = square.mirror();
-        for (board, s) in [
-            (b, square),
-            (b.rotate_90(), square.rotate_90()),
-            (b.rotate_180(), square.rotate_180()),
-            (b.rotate_270(), square.rotate_270()),
-        ]
-        .iter()
-        {
-            LIGHT_MOVES.write().unwrap().insert(*board, *s);
-        }
-    });
-    fs::remove_file("bin/opening_book.gam").unwrap();
+pub fn book_move(board: Board, color: Color) -> Option<Square> {
+    let moves = match color {
+        Color::Dark => DARK_MOVES.read().unwrap(),
+        Color::Light => LIGHT_MOVES.read().unwrap(),
+    };
+    let (canonical, t) = board.canonical();
+    moves
+        .get(&canonical)
+        .map(|square| square.transform(t.inverse()))
 }
@@ -0,0 +1,65 @@
+use crate::game::base::Color;
+use crate::game::board::Board;
+use crate::game::square_set::SquareSet;
+
+// Counts the leaf nodes of the game tree `depth` plies deep. A forced pass
+// takes up a ply, and a finished game is a leaf wherever it occurs.
+pub fn perft(board: Board, color: Color, depth: u32) -> u64 {
+    let mut board = board;
+    perft_rec(&mut board, color, depth, false)
+}
+
+fn perft_rec(board: &mut Board, color: Color, depth: u32, passed: bool) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let flippables = board.flippable_squares(color);
+    if flippables == 0 {
+        if passed {
+            // neither side can move.
+            return 1;
+        }
+        return perft_rec(board, color.opposite(), depth - 1, true);
+    }
+    if depth == 1 {
+        return flippables.count_ones() as u64;
+    }
+    let mut ret = 0;
+    for square in SquareSet(flippables).indices() {
+        let undo = board.make(square, color);
+        ret += perft_rec(board, color.opposite(), depth - 1, false);
+        board.unmake(undo);
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Reference counts for the standard starting position.
+    const EXPECTED: [u64; 13] = [
+        1, 4, 12, 56, 244, 1396, 8200, 55092, 390216, 3005288, 24571284, 212258800, 1939886636,
+    ];
+
+    #[test]
+    fn initial() {
+        for (depth, &expected) in EXPECTED.iter().enumerate().take(12) {
+            assert_eq!(
+                perft(Board::initial(), Color::Dark, depth as u32),
+                expected,
+                "depth {}",
+                depth
+            );
+        }
+    }
+
+    // takes a while in debug builds.
+    #[test]
+    #[ignore]
+    fn initial_deep() {
+        for (depth, &expected) in EXPECTED.iter().enumerate().skip(12) {
+            assert_eq!(perft(Board::initial(), Color::Dark, depth as u32), expected);
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use crate::game::base::Color;
+use crate::game::board::Board;
+use crate::game::moves::Move;
+use crate::game::strategy::{NegaScout, Strategy};
+
+// Background search run while the opponent is thinking. The opponent's reply
+// is predicted and our answer to it is searched in advance.
+pub struct Ponder {
+    predicted: Board,
+    strategy: Arc<dyn Strategy + Send + Sync>,
+    handle: JoinHandle<Move>,
+}
+
+impl Ponder {
+    // `board` is the position with the opponent to move.
+    pub fn predict(board: Board, color: Color) -> Board {
+        match NegaScout::emergency_move(board, color.opposite()) {
+            Some(square) => board.flip(square.to_uint(), color.opposite()),
+            None => board,
+        }
+    }
+
+    pub fn start(
+        predicted: Board,
+        color: Color,
+        strategy: Box<dyn Strategy + Send + Sync>,
+    ) -> Self {
+        let strategy: Arc<dyn Strategy + Send + Sync> = Arc::from(strategy);
+        let searcher = Arc::clone(&strategy);
+        let handle = thread::spawn(move || searcher.next_move(predicted, color));
+        Self {
+            predicted,
+            strategy,
+            handle,
+        }
+    }
+
+    // Returns the pondered move if `board` is the predicted position, waiting
+    // for the search to finish. Otherwise the search is aborted.
+    pub fn finish(self, board: Board) -> Option<Move> {
+        if board != self.predicted {
+            self.abort();
+            return None;
+        }
+        println!("Ponder hit.");
+        self.handle.join().ok()
+    }
+
+    pub fn abort(self) {
+        self.strategy.abort();
+        let _ = self.handle.join();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn hit() {
+        let board = Board::initial();
+        let predicted = Ponder::predict(board, Color::Light);
+        assert_eq!(predicted.empty_squares_count(), 59);
+        let ponder = Ponder::start(predicted, Color::Light, Box::new(NegaScout::new(50, None)));
+        let mv = ponder.finish(predicted).unwrap();
+        assert!(mv.is_legal(predicted, Color::Light));
+    }
+
+    #[test]
+    fn miss() {
+        let board = Board::initial();
+        let predicted = Ponder::predict(board, Color::Light);
+        let ponder = Ponder::start(
+            predicted,
+            Color::Light,
+            Box::new(NegaScout::new(60_000, None)),
+        );
+        let now = Instant::now();
+        assert_eq!(ponder.finish(board), None);
+        assert!(now.elapsed().as_secs() < 10);
+    }
+}
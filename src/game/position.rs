@@ -0,0 +1,137 @@
+use crate::error::{Error, Result};
+use crate::game::base::Color;
+use crate::game::board::Board;
+use crate::game::moves::Move;
+use crate::game::square::Square;
+use crate::game::square_set::SquareSet;
+
+// A board together with whose turn it is and how many passes led up to it.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Position {
+    pub board: Board,
+    pub color: Color,
+    passes: u8,
+}
+
+impl Position {
+    pub fn new(board: Board, color: Color) -> Self {
+        Self {
+            board,
+            color,
+            passes: 0,
+        }
+    }
+
+    pub fn initial() -> Self {
+        Self::new(Board::initial(), Color::Dark)
+    }
+
+    // Consecutive passes just before this position.
+    pub fn passes(&self) -> u8 {
+        self.passes
+    }
+
+    // A lone pass when the side to move is stuck, nothing once the game is over.
+    pub fn legal_moves(&self) -> Vec<Move> {
+        let flippables = self.board.flippable_squares(self.color);
+        if flippables == 0 {
+            if self.is_game_over() {
+                return Vec::new();
+            }
+            return vec![Move::Pass];
+        }
+        SquareSet(flippables).iter().map(Move::Square).collect()
+    }
+
+    pub fn play(&mut self, square: Square) -> Result<()> {
+        if self.board.flippable_squares(self.color) & 1 << square.to_uint() == 0 {
+            return Err(Error::IllegalMove(Move::Square(square)));
+        }
+        self.board = self.board.flip(square.to_uint(), self.color);
+        self.color = self.color.opposite();
+        self.passes = 0;
+        Ok(())
+    }
+
+    pub fn pass(&mut self) -> Result<()> {
+        if self.board.flippable_squares(self.color) != 0 || self.is_game_over() {
+            return Err(Error::IllegalMove(Move::Pass));
+        }
+        self.color = self.color.opposite();
+        self.passes += 1;
+        Ok(())
+    }
+
+    pub fn apply(&mut self, mv: Move) -> Result<()> {
+        match mv {
+            Move::Square(square) => self.play(square),
+            Move::Pass => self.pass(),
+        }
+    }
+
+    // Neither side has a move, whether or not the board is full.
+    pub fn is_game_over(&self) -> bool {
+        self.passes >= 2
+            || (self.board.flippable_squares(self.color) == 0
+                && self.board.flippable_squares(self.color.opposite()) == 0)
+    }
+
+    // Disc differential for the side to move, with the empty squares going to
+    // the winner.
+    pub fn final_score(&self) -> i8 {
+        let (own, other) = self.board.target_boards(self.color);
+        let own = own.count_ones() as i8;
+        let other = other.count_ones() as i8;
+        let empty = self.board.empty_squares_count() as i8;
+        if own > other {
+            own - other + empty
+        } else if own < other {
+            own - other - empty
+        } else {
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn play_and_pass() {
+        let mut position = Position::initial();
+        assert_eq!(position.legal_moves().len(), 4);
+        assert!(position.pass().is_err());
+        assert!(position.play(Square::from_str("A1").unwrap()).is_err());
+        position.play(Square::from_str("F5").unwrap()).unwrap();
+        assert_eq!(position.color, Color::Light);
+
+        // light has no move but dark does.
+        let board = Board::from_str(&format!("XXXO{}", "-".repeat(60))).unwrap();
+        let mut position = Position::new(board, Color::Light);
+        assert_eq!(position.legal_moves(), vec![Move::Pass]);
+        assert!(!position.is_game_over());
+        position.pass().unwrap();
+        assert_eq!(position.passes(), 1);
+        position.apply(Move::from_str("E1").unwrap()).unwrap();
+        assert_eq!(position.passes(), 0);
+        assert!(position.is_game_over());
+        assert!(position.legal_moves().is_empty());
+        assert!(position.pass().is_err());
+    }
+
+    #[test]
+    fn final_score() {
+        // 5 dark, 0 light and 59 empties.
+        let board = Board::from_str(&format!("XXXXX{}", "-".repeat(59))).unwrap();
+        assert_eq!(Position::new(board, Color::Dark).final_score(), 64);
+        assert_eq!(Position::new(board, Color::Light).final_score(), -64);
+
+        let board = Board::from_str(&format!("XXOO{}", "-".repeat(60))).unwrap();
+        assert_eq!(Position::new(board, Color::Dark).final_score(), 0);
+
+        let board = Board::new(0xffff_ffff_ffff_0000, 0xff);
+        assert_eq!(Position::new(board, Color::Dark).final_score(), 48);
+    }
+}
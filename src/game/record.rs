@@ -0,0 +1,194 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::{Error, Result};
+use crate::game::base::Color;
+use crate::game::board::Board;
+use crate::game::moves::Move;
+use crate::game::position::Position;
+use crate::game::square::Square;
+
+// A game as a list of moves from a starting position. Passes are moves of
+// their own and are inserted automatically when the side to move has none.
+#[derive(Clone, Debug)]
+pub struct GameRecord {
+    // positions[i] is the position before moves[i].
+    positions: Vec<Position>,
+    moves: Vec<Move>,
+    // number of moves currently played; the rest can be redone.
+    ply: usize,
+}
+
+impl Default for GameRecord {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GameRecord {
+    pub fn new() -> Self {
+        Self::from_position(Board::initial(), Color::Dark)
+    }
+
+    pub fn from_position(board: Board, color: Color) -> Self {
+        Self {
+            positions: vec![Position::new(board, color)],
+            moves: Vec::new(),
+            ply: 0,
+        }
+    }
+
+    pub fn position(&self) -> Position {
+        self.positions[self.ply]
+    }
+
+    pub fn board(&self) -> Board {
+        self.position().board
+    }
+
+    pub fn side_to_move(&self) -> Color {
+        self.position().color
+    }
+
+    pub fn ply(&self) -> usize {
+        self.ply
+    }
+
+    // The moves played so far, without the ones that could be redone.
+    pub fn moves(&self) -> &[Move] {
+        &self.moves[..self.ply]
+    }
+
+    // The position after `ply` moves.
+    pub fn position_at(&self, ply: usize) -> Option<Position> {
+        if ply > self.ply {
+            return None;
+        }
+        self.positions.get(ply).cloned()
+    }
+
+    pub fn is_game_over(&self) -> bool {
+        self.position().is_game_over()
+    }
+
+    // Plays `mv`, passing for the side to move first if that is the only way
+    // `mv` can be legal. Any moves that could be redone are dropped.
+    pub fn play(&mut self, mv: Move) -> Result<()> {
+        let mut next = self.position();
+        if next.apply(mv).is_err() {
+            next = self.position();
+            if mv == Move::Pass || next.pass().is_err() || next.apply(mv).is_err() {
+                return Err(Error::IllegalMove(mv));
+            }
+            self.push(Move::Pass);
+        }
+        self.push(mv);
+        Ok(())
+    }
+
+    fn push(&mut self, mv: Move) {
+        let mut next = self.position();
+        next.apply(mv).unwrap();
+        self.positions.truncate(self.ply + 1);
+        self.moves.truncate(self.ply);
+        self.positions.push(next);
+        self.moves.push(mv);
+        self.ply += 1;
+    }
+
+    pub fn undo(&mut self) -> Option<Move> {
+        if self.ply == 0 {
+            return None;
+        }
+        self.ply -= 1;
+        Some(self.moves[self.ply])
+    }
+
+    pub fn redo(&mut self) -> Option<Move> {
+        let mv = *self.moves.get(self.ply)?;
+        self.ply += 1;
+        Some(mv)
+    }
+}
+
+// Transcripts list the squares played, e.g. `f5d6c3d3c4`; passes are implied.
+impl FromStr for GameRecord {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut record = Self::new();
+        let chars: Vec<char> = s.chars().filter(|c| !c.is_whitespace()).collect();
+        for pair in chars.chunks(2) {
+            let pair: String = pair.iter().collect();
+            let square = Square::from_str(&pair).map_err(|_| Error::BadSquare(pair.clone()))?;
+            record.play(Move::Square(square))?;
+        }
+        Ok(record)
+    }
+}
+
+impl fmt::Display for GameRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for square in self.moves().iter().filter_map(|mv| mv.square()) {
+            write!(f, "{}", square.to_string().to_lowercase())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transcript() {
+        let s = "f5d6c3d3c4f4f6f3e6e7d7c5b6";
+        let record = GameRecord::from_str(s).unwrap();
+        assert_eq!(record.ply(), 13);
+        assert_eq!(record.to_string(), s);
+        assert_eq!(record.side_to_move(), Color::Light);
+        assert_eq!(
+            record.position_at(1).unwrap().board,
+            Board::initial().flip(Square::from_str("F5").unwrap().to_uint(), Color::Dark)
+        );
+
+        assert!(GameRecord::from_str("f5f5").is_err());
+        assert!(GameRecord::from_str("f5z9").is_err());
+        assert!(GameRecord::from_str("f5d").is_err());
+    }
+
+    #[test]
+    fn pass() {
+        // light has no move, so dark plays twice in a row.
+        let board = Board::from_str(&format!("XXXO{}", "-".repeat(60))).unwrap();
+        let mut record = GameRecord::from_position(board, Color::Light);
+        let e1 = Move::from_str("E1").unwrap();
+        assert!(record.play(Move::from_str("F1").unwrap()).is_err());
+        record.play(e1).unwrap();
+        assert_eq!(record.moves(), &[Move::Pass, e1]);
+        assert_eq!(record.side_to_move(), Color::Light);
+        assert!(record.is_game_over());
+        assert!(record.play(Move::Pass).is_err());
+        assert_eq!(record.to_string(), "e1");
+    }
+
+    #[test]
+    fn undo_redo() {
+        let mut record = GameRecord::from_str("f5d6c3").unwrap();
+        let board = record.board();
+        assert_eq!(record.undo(), Some(Move::from_str("C3").unwrap()));
+        assert_eq!(record.undo(), Some(Move::from_str("D6").unwrap()));
+        assert_eq!(record.redo(), Some(Move::from_str("D6").unwrap()));
+        assert_eq!(record.redo(), Some(Move::from_str("C3").unwrap()));
+        assert_eq!(record.redo(), None);
+        assert_eq!(record.board(), board);
+
+        record.undo();
+        record.play(Move::from_str("C5").unwrap()).unwrap();
+        assert_eq!(record.to_string(), "f5d6c5");
+        assert_eq!(record.redo(), None);
+        while record.undo().is_some() {}
+        assert_eq!(record.board(), Board::initial());
+        assert_eq!(record.position_at(1), None);
+    }
+}
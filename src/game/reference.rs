@@ -0,0 +1,151 @@
+use crate::game::base::Color;
+use crate::game::board::Board;
+
+const DIRECTIONS: [(i8, i8); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+// A deliberately plain implementation of the rules, one square at a time, to
+// check the bitboard code in `Board` against. Far too slow for searching.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ReferenceBoard {
+    squares: [[Option<Color>; 8]; 8],
+}
+
+impl ReferenceBoard {
+    pub fn from_board(board: Board) -> Self {
+        let mut squares = [[None; 8]; 8];
+        for (y, row) in squares.iter_mut().enumerate() {
+            for (x, square) in row.iter_mut().enumerate() {
+                let bit = 1u64 << (x + y * 8);
+                if board.dark() & bit != 0 {
+                    *square = Some(Color::Dark);
+                } else if board.light() & bit != 0 {
+                    *square = Some(Color::Light);
+                }
+            }
+        }
+        Self { squares }
+    }
+
+    pub fn to_board(&self) -> Board {
+        let mut dark = 0;
+        let mut light = 0;
+        for (y, row) in self.squares.iter().enumerate() {
+            for (x, square) in row.iter().enumerate() {
+                match square {
+                    Some(Color::Dark) => dark |= 1u64 << (x + y * 8),
+                    Some(Color::Light) => light |= 1u64 << (x + y * 8),
+                    None => {}
+                }
+            }
+        }
+        Board::new(dark, light)
+    }
+
+    fn get(&self, x: i8, y: i8) -> Option<Option<Color>> {
+        if (0..8).contains(&x) && (0..8).contains(&y) {
+            Some(self.squares[y as usize][x as usize])
+        } else {
+            None
+        }
+    }
+
+    // Discs turned over by `color` playing at (x, y), as square indices.
+    pub fn flips(&self, x: u8, y: u8, color: Color) -> Vec<u8> {
+        let mut ret = Vec::new();
+        if self.squares[y as usize][x as usize].is_some() {
+            return ret;
+        }
+        for &(dx, dy) in DIRECTIONS.iter() {
+            let mut line = Vec::new();
+            let (mut cx, mut cy) = (x as i8 + dx, y as i8 + dy);
+            while self.get(cx, cy) == Some(Some(color.opposite())) {
+                line.push(cx as u8 + cy as u8 * 8);
+                cx += dx;
+                cy += dy;
+            }
+            if self.get(cx, cy) == Some(Some(color)) {
+                ret.extend(line);
+            }
+        }
+        ret.sort_unstable();
+        ret
+    }
+
+    // Square indices `color` can play on.
+    pub fn legal_moves(&self, color: Color) -> Vec<u8> {
+        (0..64)
+            .filter(|&i| !self.flips(i % 8, i / 8, color).is_empty())
+            .collect()
+    }
+
+    pub fn play(&mut self, square: u8, color: Color) {
+        let flips = self.flips(square % 8, square / 8, color);
+        assert!(!flips.is_empty(), "illegal move");
+        for s in flips.into_iter().chain(std::iter::once(square)) {
+            self.squares[s as usize / 8][s as usize % 8] = Some(color);
+        }
+    }
+
+    pub fn is_game_over(&self) -> bool {
+        self.legal_moves(Color::Dark).is_empty() && self.legal_moves(Color::Light).is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::position::Position;
+    use crate::game::square::Square;
+    use crate::game::util::XorShift;
+
+    fn bits(squares: &[u8]) -> u64 {
+        squares.iter().fold(0, |acc, &s| acc | 1 << s)
+    }
+
+    // Plays random games on both implementations and compares them at every
+    // ply.
+    #[test]
+    fn random_games() {
+        let mut rng = XorShift::new(0x2545_f491_4f6c_dd1d);
+        for _ in 0..2000 {
+            let mut position = Position::initial();
+            let mut reference = ReferenceBoard::from_board(position.board);
+            loop {
+                let board = position.board;
+                let color = position.color;
+                assert_eq!(reference.to_board(), board);
+                let legal = reference.legal_moves(color);
+                assert_eq!(bits(&legal), board.flippable_squares(color), "{:?}", board);
+                for &square in legal.iter() {
+                    assert_eq!(
+                        bits(&reference.flips(square % 8, square / 8, color)),
+                        board.flipped_squares(square, color),
+                        "{:?} {}",
+                        board,
+                        square
+                    );
+                }
+                assert_eq!(reference.is_game_over(), position.is_game_over());
+                if position.is_game_over() {
+                    break;
+                }
+                if legal.is_empty() {
+                    position.pass().unwrap();
+                    continue;
+                }
+                let square = legal[rng.next_u64() as usize % legal.len()];
+                reference.play(square, color);
+                position.play(Square::from_uint(square)).unwrap();
+            }
+        }
+    }
+}
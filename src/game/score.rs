@@ -1,5 +1,6 @@
 use super::board::Board;
 use crate::game::base::Color;
+use crate::game::square_set::SquareSet;
 
 type Weight = [i16; 4];
 
@@ -112,8 +113,8 @@ impl Board {
     #[inline]
     fn openness_score(&self, mv: u8, color: Color) -> i16 {
         let flipped = self.flipped_squares(mv, color);
-        let openness = (0..64)
-            .filter(|x| flipped & 1_u64 << x != 0)
+        let openness = SquareSet(flipped)
+            .indices()
             .fold(0_u64, |ret, s| ret + self.openness_of_square(s)) as i16;
         openness * self.get_weight(Self::OPENNESS_WEIGHT)
     }
@@ -121,11 +122,11 @@ impl Board {
     #[inline]
     fn openness_of_square(&self, square: u8) -> u64 {
         let square = 1_u64 << square as u64;
-        let blank = !(self.dark | self.light);
+        let blank = !(self.dark() | self.light());
         let mut bb = (square << 1 & (blank & 0xfefefefefefefefe))
             | (square >> 1 & (blank & 0x7f7f7f7f7f7f7f7f))
-            | (square << 8 & (blank & 0xffffffffffffffff))
-            | (square >> 8 & (blank & 0xffffffffffffffff))
+            | (square << 8 & blank)
+            | (square >> 8 & blank)
             | (square << 7 & (blank & 0x7f7f7f7f7f7f7f7f))
             | (square >> 7 & (blank & 0xfefefefefefefefe))
             | (square << 9 & (blank & 0xfefefefefefefefe))
@@ -141,12 +142,12 @@ impl Board {
     #[inline]
     fn raw_score(&self, color: Color) -> i16 {
         let (target, opponent) = self.target_boards(color);
-        let raw = (0..64)
-            .filter(|i| target & 1_u64 << i != 0)
-            .fold(0, |ret, i| ret + Self::RAW_VALUES[i])
-            - (0..64)
-                .filter(|i| opponent & 1_u64 << i != 0)
-                .fold(0, |ret, i| ret + Self::RAW_VALUES[i])
+        let raw = SquareSet(target)
+            .indices()
+            .fold(0, |ret, i| ret + Self::RAW_VALUES[i as usize])
+            - SquareSet(opponent)
+                .indices()
+                .fold(0, |ret, i| ret + Self::RAW_VALUES[i as usize])
             + self.corner_flipped_score(color)
             - self.corner_flipped_score(color.opposite());
 
@@ -286,8 +287,8 @@ impl Board {
     #[inline]
     fn disks_of_color(&self, color: Color) -> u64 {
         match color {
-            Color::Dark => self.dark,
-            Color::Light => self.light,
+            Color::Dark => self.dark(),
+            Color::Light => self.light(),
         }
     }
 
@@ -311,6 +312,7 @@ impl Board {
 mod tests {
     use super::*;
     use crate::game::square::Square;
+    use std::str::FromStr;
 
     #[test]
     fn raw_score() {
@@ -320,19 +322,13 @@ mod tests {
 
     #[test]
     fn mountain_score() {
-        let board = Board {
-            dark: 0x7e3d81818181817e,
-            light: 0,
-        };
+        let board = Board::new(0x7e3d81818181817e, 0);
         assert_eq!(
             board.mountain_score(Color::Dark),
             board.get_weight(Board::MOUNTAIN_WEIGHT) * 2
-                + board.get_weight(Board::PURE_MOUNTAIN_WEIGHT) * 1
+                + board.get_weight(Board::PURE_MOUNTAIN_WEIGHT)
         );
-        let board = Board {
-            dark: 0x7e3d81818181817e,
-            light: 1,
-        };
+        let board = Board::new(0x7e3d81818181817e, 1);
         assert_eq!(
             board.mountain_score(Color::Dark),
             board.get_weight(Board::PURE_MOUNTAIN_WEIGHT)
@@ -344,35 +340,20 @@ mod tests {
         let board = Board::initial();
         assert_eq!(board.solid_disks_count(Color::Light), 0);
 
-        let board = Board {
-            dark: 0x0000783c465c3c7e,
-            light: 0x008080c0b8a0c080,
-        };
+        let board = Board::new(0x0000783c465c3c7e, 0x008080c0b8a0c080);
         assert_eq!(board.solid_disks_count(Color::Light), 7);
 
-        let board = Board {
-            dark: 0x0000783c465c3c7e,
-            light: 0x008080c0b8a04080,
-        };
+        let board = Board::new(0x0000783c465c3c7e, 0x008080c0b8a04080);
         assert_eq!(board.solid_disks_count(Color::Light), 1);
 
-        let board = Board {
-            dark: 0x0000e83c465c3c7e,
-            light: 0x008000c0b8a0c080,
-        };
+        let board = Board::new(0x0000e83c465c3c7e, 0x008000c0b8a0c080);
         assert_eq!(board.solid_disks_count(Color::Light), 5);
 
-        let board = Board {
-            dark: 0x0000783c465c3cee,
-            light: 0x000080c0b8a0c000,
-        };
+        let board = Board::new(0x0000783c465c3cee, 0x000080c0b8a0c000);
         assert_eq!(board.solid_disks_count(Color::Dark), 3);
         assert_eq!(board.solid_disks_count(Color::Light), 0);
 
-        let board = Board {
-            dark: 0x8080e83c465c3c7e,
-            light: 0x000000c0b8a0c080,
-        };
+        let board = Board::new(0x8080e83c465c3c7e, 0x000000c0b8a0c080);
         board.print();
         assert_eq!(board.solid_disks_count(Color::Light), 5);
         assert_eq!(board.solid_disks_count(Color::Dark), 3);
@@ -394,10 +375,7 @@ mod tests {
             5 * board.get_weight(Board::OPENNESS_WEIGHT)
         );
 
-        let board = Board {
-            dark: 0x000014f840200000,
-            light: 0x0000200438181000,
-        };
+        let board = Board::new(0x000014f840200000, 0x0000200438181000);
         let f2 = Square::from_str("F2").unwrap().to_uint();
         assert_eq!(
             board.openness_score(f2, Color::Light),
@@ -411,7 +389,7 @@ mod tests {
         let g6 = Square::from_str("G6").unwrap().to_uint();
         assert_eq!(
             board.openness_score(g6, Color::Light),
-            1 * board.get_weight(Board::OPENNESS_WEIGHT)
+            board.get_weight(Board::OPENNESS_WEIGHT)
         );
     }
 }
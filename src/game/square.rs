@@ -1,3 +1,8 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::game::symmetry::Transform;
+
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub struct Square {
     // x and y are both 0 indexed
@@ -6,37 +11,14 @@ pub struct Square {
 }
 
 impl Square {
-    pub fn from_str(s: &str) -> Result<Self, &str> {
-        let mut chars = s.chars();
-        let x = match chars.next() {
-            Some(c) => {
-                let base = if c.is_uppercase() { 'A' } else { 'a' };
-                c as u8 - base as u8
-            }
-            None => return Err("While parsing Square: invalid square."),
-        };
-        let y = match chars.next() {
-            Some(c) => match c.to_digit(10) {
-                Some(n) => n as u8 - 1,
-                None => return Err("While parsing Square: invalid square."),
-            },
-            None => return Err("While parsing Square: invalid square."),
-        };
-        Ok(Self { x, y })
-    }
-
     pub fn from_uint(i: u8) -> Self {
         Self { x: i % 8, y: i / 8 }
     }
 
-    pub fn to_uint(&self) -> u8 {
+    pub fn to_uint(self) -> u8 {
         self.x + self.y * 8
     }
 
-    pub fn to_string(&self) -> String {
-        format!("{}{}", (self.x + 'A' as u8) as char, self.y + 1)
-    }
-
     pub fn mirror(&self) -> Self {
         Self {
             x: self.x,
@@ -58,6 +40,38 @@ impl Square {
     pub fn rotate_270(&self) -> Self {
         self.rotate_180().rotate_90()
     }
+
+    pub fn transform(&self, t: Transform) -> Self {
+        let (x, y) = t.apply_xy(self.x, self.y);
+        Self { x, y }
+    }
+}
+
+impl FromStr for Square {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars();
+        let x = match chars.next() {
+            Some(c @ 'A'..='H') => c as u8 - b'A',
+            Some(c @ 'a'..='h') => c as u8 - b'a',
+            _ => return Err("While parsing Square: invalid square."),
+        };
+        let y = match chars.next() {
+            Some(c @ '1'..='8') => c as u8 - b'1',
+            _ => return Err("While parsing Square: invalid square."),
+        };
+        if chars.next().is_some() {
+            return Err("While parsing Square: invalid square.");
+        }
+        Ok(Self { x, y })
+    }
+}
+
+impl fmt::Display for Square {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", (self.x + b'A') as char, self.y + 1)
+    }
 }
 
 #[cfg(test)]
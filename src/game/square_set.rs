@@ -0,0 +1,228 @@
+use std::iter::FromIterator;
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not, Sub};
+
+use crate::game::square::Square;
+
+// A set of squares as a bitboard, bit `x + y * 8` standing for (x, y).
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct SquareSet(pub u64);
+
+impl SquareSet {
+    pub const EMPTY: SquareSet = SquareSet(0);
+    pub const ALL: SquareSet = SquareSet(!0);
+
+    #[inline]
+    pub fn bits(self) -> u64 {
+        self.0
+    }
+
+    #[inline]
+    pub fn count(self) -> u32 {
+        self.0.count_ones()
+    }
+
+    #[inline]
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    #[inline]
+    pub fn contains(self, square: Square) -> bool {
+        self.0 & 1 << square.to_uint() != 0
+    }
+
+    #[inline]
+    pub fn insert(&mut self, square: Square) {
+        self.0 |= 1 << square.to_uint();
+    }
+
+    #[inline]
+    pub fn remove(&mut self, square: Square) {
+        self.0 &= !(1 << square.to_uint());
+    }
+
+    // The square with the lowest index.
+    #[inline]
+    pub fn first(self) -> Option<Square> {
+        self.indices().next().map(Square::from_uint)
+    }
+
+    // Square indices in increasing order, for code working on raw `u8`s.
+    #[inline]
+    pub fn indices(self) -> Indices {
+        Indices(self.0)
+    }
+
+    #[inline]
+    pub fn iter(self) -> Iter {
+        Iter(self.indices())
+    }
+}
+
+// Visits only the set bits, lowest first.
+#[derive(Clone, Debug)]
+pub struct Indices(u64);
+
+impl Iterator for Indices {
+    type Item = u8;
+
+    #[inline]
+    fn next(&mut self) -> Option<u8> {
+        if self.0 == 0 {
+            return None;
+        }
+        let ret = self.0.trailing_zeros() as u8;
+        self.0 &= self.0 - 1;
+        Some(ret)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let count = self.0.count_ones() as usize;
+        (count, Some(count))
+    }
+}
+
+impl ExactSizeIterator for Indices {}
+
+#[derive(Clone, Debug)]
+pub struct Iter(Indices);
+
+impl Iterator for Iter {
+    type Item = Square;
+
+    #[inline]
+    fn next(&mut self) -> Option<Square> {
+        self.0.next().map(Square::from_uint)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl ExactSizeIterator for Iter {}
+
+impl IntoIterator for SquareSet {
+    type Item = Square;
+    type IntoIter = Iter;
+
+    fn into_iter(self) -> Iter {
+        self.iter()
+    }
+}
+
+impl FromIterator<Square> for SquareSet {
+    fn from_iter<I: IntoIterator<Item = Square>>(iter: I) -> Self {
+        let mut ret = SquareSet::EMPTY;
+        for square in iter {
+            ret.insert(square);
+        }
+        ret
+    }
+}
+
+impl From<u64> for SquareSet {
+    fn from(bits: u64) -> Self {
+        SquareSet(bits)
+    }
+}
+
+impl From<Square> for SquareSet {
+    fn from(square: Square) -> Self {
+        SquareSet(1 << square.to_uint())
+    }
+}
+
+impl BitAnd for SquareSet {
+    type Output = SquareSet;
+
+    fn bitand(self, rhs: SquareSet) -> SquareSet {
+        SquareSet(self.0 & rhs.0)
+    }
+}
+
+impl BitOr for SquareSet {
+    type Output = SquareSet;
+
+    fn bitor(self, rhs: SquareSet) -> SquareSet {
+        SquareSet(self.0 | rhs.0)
+    }
+}
+
+impl BitXor for SquareSet {
+    type Output = SquareSet;
+
+    fn bitxor(self, rhs: SquareSet) -> SquareSet {
+        SquareSet(self.0 ^ rhs.0)
+    }
+}
+
+impl Sub for SquareSet {
+    type Output = SquareSet;
+
+    fn sub(self, rhs: SquareSet) -> SquareSet {
+        SquareSet(self.0 & !rhs.0)
+    }
+}
+
+impl Not for SquareSet {
+    type Output = SquareSet;
+
+    fn not(self) -> SquareSet {
+        SquareSet(!self.0)
+    }
+}
+
+impl BitAndAssign for SquareSet {
+    fn bitand_assign(&mut self, rhs: SquareSet) {
+        self.0 &= rhs.0;
+    }
+}
+
+impl BitOrAssign for SquareSet {
+    fn bitor_assign(&mut self, rhs: SquareSet) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl BitXorAssign for SquareSet {
+    fn bitxor_assign(&mut self, rhs: SquareSet) {
+        self.0 ^= rhs.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn iterate() {
+        let set = SquareSet(0x8000_0000_0000_0081);
+        assert_eq!(set.indices().collect::<Vec<_>>(), vec![0, 7, 63]);
+        assert_eq!(set.iter().len(), 3);
+        let squares: Vec<String> = set.into_iter().map(|s| s.to_string()).collect();
+        assert_eq!(squares, vec!["A1", "H1", "H8"]);
+        assert_eq!(set.iter().collect::<SquareSet>(), set);
+        assert_eq!(SquareSet::EMPTY.first(), None);
+        assert_eq!(set.first(), Some(Square::from_str("A1").unwrap()));
+    }
+
+    #[test]
+    fn operations() {
+        let a1 = Square::from_str("A1").unwrap();
+        let mut set = SquareSet::from(a1);
+        assert!(set.contains(a1));
+        set.insert(Square::from_str("B1").unwrap());
+        assert_eq!(set.count(), 2);
+        assert_eq!(set - SquareSet::from(a1), SquareSet(0b10));
+        assert_eq!(set & SquareSet(0b1), SquareSet(0b1));
+        assert_eq!(set | SquareSet(0b100), SquareSet(0b111));
+        assert_eq!((!set).count(), 62);
+        set.remove(a1);
+        set ^= SquareSet(0b10);
+        assert!(set.is_empty());
+    }
+}
@@ -1,15 +1,27 @@
 use crate::game::base::Color;
 use crate::game::board::Board;
-use crate::game::square::Square;
+use crate::game::moves::Move;
 
 pub mod exhausive;
+pub mod info;
 pub mod naive;
 pub mod nega_scout;
+pub mod transposition;
 
 pub use exhausive::Exhausive;
+pub use info::SearchInfo;
 pub use naive::Naive;
 pub use nega_scout::NegaScout;
 
 pub trait Strategy {
-    fn next_move(&self, board: Board, color: Color) -> Option<Square>;
+    // Must return `Move::Pass` exactly when `color` has no legal square.
+    fn next_move(&self, board: Board, color: Color) -> Move;
+
+    // Asks a running `next_move` on another thread to return as soon as possible.
+    fn abort(&self) {}
+
+    // What the last search expected, if it can tell.
+    fn last_info(&self) -> Option<SearchInfo> {
+        None
+    }
 }
@@ -1,215 +1,515 @@
-use lazy_static::lazy_static;
-use std::collections::HashMap;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::RwLock;
+use std::cmp;
+use std::sync::atomic::{AtomicBool, AtomicI8, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
 use std::time::{Duration, Instant};
 
 use super::NegaScout;
 use crate::game::base::Color;
 use crate::game::board::Board;
+use crate::game::moves::Move;
+use crate::game::position::Position;
 use crate::game::square::Square;
+use crate::game::square_set::SquareSet;
+use crate::game::strategy::info::{InfoCallback, SearchInfo};
+use crate::game::strategy::transposition::{
+    endgame_key, shared_table, Bound, Entry, TranspositionTable,
+};
 use crate::game::strategy::Strategy;
 
-lazy_static! {
-    pub static ref WINNABLE_COLOR_HISTORY: RwLock<HashMap<(Board, Color), Option<Color>>> =
-        RwLock::new(HashMap::new());
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SolveMode {
+    // The final disc differential.
+    Exact,
+    // Only whether the side to move wins, loses or draws, which is faster.
+    WinLossDraw,
 }
 
+// Solves the game to the end. Scores are final disc differentials for the side
+// to move, with the empty squares going to the winner.
 pub struct Exhausive {
     pub should_stop: AtomicBool,
+    // unlike a timeout, an abort doesn't fall back to NegaScout.
+    pub aborted: AtomicBool,
     pub time_limit: Duration,
     pub now: Instant,
+    pub mode: SolveMode,
+    pub table: Arc<TranspositionTable>,
+    threads: usize,
+    nodes: AtomicU64,
+    info_callback: Option<InfoCallback>,
+    last_info: Mutex<Option<SearchInfo>>,
 }
 
 impl Strategy for Exhausive {
-    fn next_move(&self, board: Board, color: Color) -> Option<Square> {
+    fn next_move(&self, board: Board, color: Color) -> Move {
         let flippables = board.flippable_squares(color);
         if flippables == 0 {
-            return None;
+            return Move::Pass;
         }
-        let mut ret = None;
-        for square in order_moves(board, color, flippables) {
-            let next_board = board.flip(square, color);
-            match self.winnable_color(next_board, color.opposite(), false) {
-                Some(c) => {
-                    if c == color {
-                        return Some(Square::from_uint(square));
-                    }
+        match self.solve(board, color) {
+            Some((mv, score)) => {
+                if score < 0 {
+                    eprint!("LOSE color: {:?}\n{}", color, board.diagram());
                 }
-                None => ret = ret.or(Some(Square::from_uint(square))),
+                mv
             }
-
-            self.check_time_limit();
-            if self.should_stop.load(Ordering::Relaxed) {
-                return self.switch_to_nega_scout(board, color);
+            None if self.aborted.load(Ordering::Relaxed) => {
+                Move::Square(SquareSet(flippables).first().unwrap())
             }
+            None => self.switch_to_nega_scout(board, color),
         }
-        if ret.is_none() {
-            println!("LOSE color: {:?}", color);
-            board.print();
-        }
-        ret.or(Some(Square::from_uint(flippables.trailing_zeros() as u8)))
+    }
+
+    fn abort(&self) {
+        self.aborted.store(true, Ordering::Relaxed);
+        self.should_stop.store(true, Ordering::Relaxed);
+    }
+
+    fn last_info(&self) -> Option<SearchInfo> {
+        self.last_info.lock().unwrap().clone()
     }
 }
 
 impl Exhausive {
+    // Below this many empty squares the table costs more than it saves.
+    const MIN_TABLE_EMPTIES: u32 = 7;
+    // Moves are sorted by the opponent's mobility above this many empties.
+    const MIN_ORDERING_EMPTIES: u32 = 6;
+    // What helpers see as alpha until the first root move is solved.
+    const NO_ALPHA: i8 = i8::MIN;
+
     pub fn new(time_limit_millisec: u64) -> Self {
         Self {
             should_stop: AtomicBool::new(false),
+            aborted: AtomicBool::new(false),
             now: Instant::now(),
             time_limit: Duration::from_millis(time_limit_millisec),
+            mode: SolveMode::Exact,
+            table: shared_table(),
+            threads: 1,
+            nodes: AtomicU64::new(0),
+            info_callback: None,
+            last_info: Mutex::new(None),
         }
     }
 
-    fn winnable_color(&self, board: Board, hand: Color, passed: bool) -> Option<Color> {
-        let read = WINNABLE_COLOR_HISTORY.read().unwrap();
-        if let Some(c) = read.get(&(board, hand)) {
-            return *c;
-        }
-        drop(read);
+    pub fn set_mode(&mut self, mode: SolveMode) {
+        self.mode = mode;
+    }
 
-        self.check_time_limit();
-        if self.should_stop.load(Ordering::Relaxed) {
-            return None;
-        }
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = cmp::max(threads, 1);
+    }
 
-        if board.is_last_move() {
-            let winner = self.winnable_color_last(board, hand, passed);
-            self.write_lock(board, hand, winner);
-            return winner;
-        } else if board.is_end() {
-            let winner = board.winner();
-            self.write_lock(board, hand, winner);
-            return winner;
-        }
+    // Called once the move is decided, or passed on to NegaScout on a timeout.
+    pub fn set_info_callback(&mut self, callback: InfoCallback) {
+        self.info_callback = Some(callback);
+    }
 
-        self.check_time_limit();
+    // The best move of `color` and its score, or `None` if the search was
+    // stopped. In `SolveMode::WinLossDraw` the score is only 1, 0 or -1.
+    pub fn solve(&self, board: Board, color: Color) -> Option<(Move, i8)> {
+        let (alpha, beta) = match self.mode {
+            SolveMode::Exact => (-65, 65),
+            SolveMode::WinLossDraw => (-1, 1),
+        };
+        // once we have the first root move's score, helpers take the moves
+        // after it and test them against our alpha with null windows, leaving
+        // the results in the table for when we get there.
+        let helpers: Vec<Exhausive> = (1..self.threads).map(|_| self.helper()).collect();
+        let moves = Self::order_moves(board, color, board.flippable_squares(color));
+        let next = AtomicUsize::new(1);
+        let shared_alpha = AtomicI8::new(Self::NO_ALPHA);
+        let mut pv = Vec::new();
+        let score = thread::scope(|scope| {
+            for helper in helpers.iter() {
+                let (moves, next, shared_alpha) = (&moves, &next, &shared_alpha);
+                scope.spawn(move || helper.help(board, color, moves, next, shared_alpha));
+            }
+            let score = if moves.is_empty() {
+                self.negamax(board, color, alpha, beta, &mut pv)
+            } else {
+                self.search_root(board, color, (alpha, beta), &moves, &shared_alpha, &mut pv)
+            };
+            for helper in helpers.iter() {
+                helper.should_stop.store(true, Ordering::Relaxed);
+            }
+            score
+        });
         if self.should_stop.load(Ordering::Relaxed) {
             return None;
         }
-
-        let flippables = order_moves(board, hand, board.flippable_squares(hand));
-        let opposite = hand.opposite();
-        if flippables.is_empty() {
-            let winner = if passed {
-                board.winner()
-            } else {
-                self.winnable_color(board, opposite, true)
-            };
-            self.write_lock(board, hand, winner);
-            self.write_lock(board, opposite, winner);
-            return winner;
+        let score = match self.mode {
+            SolveMode::Exact => score,
+            SolveMode::WinLossDraw => score.signum(),
+        };
+        let mv = pv.first().copied().unwrap_or(Move::Pass);
+        let info = SearchInfo {
+            depth: board.empty_squares_count() as u32,
+            score: score as i16,
+            nodes: helpers
+                .iter()
+                .chain(std::iter::once(self))
+                .map(|search| search.nodes.load(Ordering::Relaxed))
+                .sum(),
+            elapsed: self.now.elapsed(),
+            pv,
+        };
+        if let Some(callback) = self.info_callback.as_ref() {
+            callback(&info);
         }
+        *self.last_info.lock().unwrap() = Some(info);
+        Some((mv, score))
+    }
 
-        self.check_time_limit();
-
-        let mut ret = Some(hand.opposite());
+    // A solver sharing our table, left to run until we stop it.
+    fn helper(&self) -> Exhausive {
+        let mut ret = Exhausive::new(u64::MAX / 2);
+        ret.mode = self.mode;
+        ret.table = Arc::clone(&self.table);
+        ret
+    }
 
-        for square in flippables.iter().cloned() {
+    // `negamax` over the root moves, publishing alpha to the helpers in
+    // `shared_alpha` whenever a move is done.
+    fn search_root(
+        &self,
+        board: Board,
+        color: Color,
+        window: (i8, i8),
+        moves: &[u8],
+        shared_alpha: &AtomicI8,
+        pv: &mut Vec<Move>,
+    ) -> i8 {
+        let (mut alpha, beta) = window;
+        let opposite = color.opposite();
+        let mut best_score = -65;
+        let mut child_pv = Vec::new();
+        for (i, &square) in moves.iter().enumerate() {
+            let next_board = board.flip(square, color);
+            let mut score = if i == 0 {
+                -self.negamax(next_board, opposite, -beta, -alpha, &mut child_pv)
+            } else {
+                -self.negamax(next_board, opposite, -alpha - 1, -alpha, &mut child_pv)
+            };
+            if i > 0 && alpha < score && score < beta {
+                score = -self.negamax(next_board, opposite, -beta, -score, &mut child_pv);
+            }
             if self.should_stop.load(Ordering::Relaxed) {
-                return None;
-            }
-            let next_board = board.flip(square, hand);
-            let next_winnable = self.winnable_color(next_board, opposite, false);
-            self.write_lock(next_board, opposite, next_winnable);
-            match next_winnable {
-                Some(c) => {
-                    if c == hand {
-                        self.write_lock(next_board, opposite, next_winnable);
-                        return Some(hand);
-                    }
-                }
-                None => ret = None,
+                return 0;
             }
 
+            if best_score < score {
+                best_score = score;
+                pv.clear();
+                pv.push(Move::Square(Square::from_uint(square)));
+                pv.extend_from_slice(&child_pv);
+            }
+            alpha = cmp::max(alpha, score);
+            shared_alpha.store(alpha, Ordering::Relaxed);
+            if alpha >= beta {
+                break;
+            }
+        }
+        best_score
+    }
+
+    // Takes root moves from `moves` by `next` until they run out, and tests
+    // each against the main thread's alpha as soon as there is one.
+    fn help(
+        &self,
+        board: Board,
+        color: Color,
+        moves: &[u8],
+        next: &AtomicUsize,
+        shared_alpha: &AtomicI8,
+    ) {
+        while shared_alpha.load(Ordering::Relaxed) == Self::NO_ALPHA {
             if self.should_stop.load(Ordering::Relaxed) {
-                return None;
+                return;
             }
-            self.check_time_limit();
+            thread::sleep(Duration::from_millis(1));
+        }
+        let mut pv = Vec::new();
+        loop {
+            let i = next.fetch_add(1, Ordering::Relaxed);
+            if i >= moves.len() || self.should_stop.load(Ordering::Relaxed) {
+                break;
+            }
+            let alpha = shared_alpha.load(Ordering::Relaxed);
+            let next_board = board.flip(moves[i], color);
+            self.negamax(next_board, color.opposite(), -alpha - 1, -alpha, &mut pv);
         }
+    }
 
-        self.write_lock(board, hand, ret);
+    fn order_moves(board: Board, color: Color, flippables: u64) -> Vec<u8> {
+        let mut ret: Vec<u8> = SquareSet(flippables).indices().collect();
+        if board.empty_squares_count() as u32 > Self::MIN_ORDERING_EMPTIES {
+            // fastest first: moves that leave the opponent little choice.
+            let opposite = color.opposite();
+            ret.sort_by_key(|&square| {
+                board
+                    .flip(square, color)
+                    .flippable_squares(opposite)
+                    .count_ones()
+            });
+        }
         ret
     }
 
-    fn winnable_color_last(&self, board: Board, hand: Color, passed: bool) -> Option<Color> {
-        let flippables = board.flippable_squares(hand);
+    // Fails soft like `NegaScout::nega_scout`. `pv` is set to the line of the
+    // best move found.
+    fn negamax(&self, board: Board, color: Color, alpha: i8, beta: i8, pv: &mut Vec<Move>) -> i8 {
+        pv.clear();
+        if self.nodes.fetch_add(1, Ordering::Relaxed) & 0x3ff == 0 {
+            self.check_time_limit();
+        }
+        if self.should_stop.load(Ordering::Relaxed) {
+            return 0;
+        }
+
+        let opposite = color.opposite();
+        let flippables = board.flippable_squares(color);
         if flippables == 0 {
-            if passed {
-                board.winner()
-            } else {
-                let opposite = hand.opposite();
-                let flippables = board.flippable_squares(opposite);
-                if flippables == 0 {
-                    board.winner()
-                } else {
-                    let pos = flippables.trailing_zeros() as u8;
-                    board.flip(pos, opposite).winner()
-                }
+            if board.flippable_squares(opposite) == 0 {
+                return Position::new(board, color).final_score();
             }
+            let score = -self.negamax(board, opposite, -beta, -alpha, pv);
+            pv.insert(0, Move::Pass);
+            return score;
+        }
+
+        let empties = board.empty_squares_count() as u32;
+        if empties == 1 {
+            let square = flippables.trailing_zeros() as u8;
+            pv.push(Move::Square(Square::from_uint(square)));
+            return -Position::new(board.flip(square, color), opposite).final_score();
+        }
+
+        let (mut alpha, mut beta) = (alpha, beta);
+        let key = endgame_key(board, color);
+        let entry = if empties >= Self::MIN_TABLE_EMPTIES {
+            self.table.probe(key)
         } else {
-            let pos = flippables.trailing_zeros() as u8;
-            board.flip(pos, hand).winner()
+            None
+        };
+        // no cut-offs inside an open window, so that the line stays whole.
+        if let Some(entry) = entry.filter(|_| alpha + 1 == beta) {
+            let score = entry.score as i8;
+            match entry.bound {
+                Bound::Exact => return score,
+                Bound::Lower => alpha = cmp::max(alpha, score),
+                Bound::Upper => beta = cmp::min(beta, score),
+            }
+            if alpha >= beta {
+                return score;
+            }
         }
-    }
+        let original_alpha = alpha;
+
+        let mut moves = Self::order_moves(board, color, flippables);
+        if let Some(best) = entry.and_then(|e| e.best_move) {
+            if let Some(i) = moves.iter().position(|&square| square == best) {
+                moves[..=i].rotate_right(1);
+            }
+        }
+
+        let mut best_score = -65;
+        let mut best_move = moves[0];
+        let mut child_pv = Vec::new();
+        for (i, &square) in moves.iter().enumerate() {
+            let next_board = board.flip(square, color);
+            let mut score = if i == 0 {
+                -self.negamax(next_board, opposite, -beta, -alpha, &mut child_pv)
+            } else {
+                -self.negamax(next_board, opposite, -alpha - 1, -alpha, &mut child_pv)
+            };
+            if i > 0 && alpha < score && score < beta {
+                score = -self.negamax(next_board, opposite, -beta, -score, &mut child_pv);
+            }
+            if self.should_stop.load(Ordering::Relaxed) {
+                return 0;
+            }
 
-    fn write_lock(&self, board: Board, color: Color, winnable: Option<Color>) {
-        let mut write = WINNABLE_COLOR_HISTORY.write().unwrap();
-        write.insert((board, color), winnable);
+            if best_score < score {
+                best_score = score;
+                best_move = square;
+                pv.clear();
+                pv.push(Move::Square(Square::from_uint(square)));
+                pv.extend_from_slice(&child_pv);
+            }
+            alpha = cmp::max(alpha, score);
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        if empties >= Self::MIN_TABLE_EMPTIES {
+            let bound = if best_score <= original_alpha {
+                Bound::Upper
+            } else if best_score >= beta {
+                Bound::Lower
+            } else {
+                Bound::Exact
+            };
+            self.table.store(
+                key,
+                Entry {
+                    depth: empties as i8,
+                    bound,
+                    score: best_score as i16,
+                    best_move: Some(best_move),
+                },
+            );
+        }
+        best_score
     }
 
     fn check_time_limit(&self) {
-        if self.now.elapsed() > self.time_limit {
-            println!("Timeout! Switching...");
-            self.should_stop.store(true, Ordering::Relaxed);
+        if self.now.elapsed() > self.time_limit && !self.should_stop.swap(true, Ordering::Relaxed) {
+            eprintln!("Timeout! Switching...");
         }
     }
 
-    fn switch_to_nega_scout(&self, board: Board, color: Color) -> Option<Square> {
+    // Only gets what is left of our own time limit; with nothing left the
+    // first iteration still gives a move.
+    fn switch_to_nega_scout(&self, board: Board, color: Color) -> Move {
         let em = NegaScout::emergency_move(board, color);
-        let rest = self
-            .time_limit
-            .checked_sub(self.now.elapsed().div_f32(4_f32))
-            .unwrap_or(Duration::new(0, 0));
-        NegaScout::new_from_duration(rest, em).next_move(board, color)
+        let rest = self.time_limit.saturating_sub(self.now.elapsed());
+        let mut search = NegaScout::new_from_duration(rest, em);
+        search.set_threads(self.threads);
+        if let Some(callback) = self.info_callback.as_ref() {
+            search.set_info_callback(callback.clone());
+        }
+        let ret = search.next_move(board, color);
+        *self.last_info.lock().unwrap() = search.last_info();
+        ret
     }
 }
 
-fn order_moves(board: Board, color: Color, flippables: u64) -> Vec<u8> {
-    let mut ret = (0..64)
-        .filter(|&x| flippables & 1 << x != 0)
-        .collect::<Vec<u8>>();
-    ret.sort_by(|a, b| {
-        let a_score = board.score(*a, color);
-        let b_score = board.score(*b, color);
-        b_score.partial_cmp(&a_score).unwrap()
-    });
-    ret
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::game::util::XorShift;
+
+    // Plain minimax over the whole game tree.
+    fn minimax(position: Position) -> i8 {
+        if position.is_game_over() {
+            return position.final_score();
+        }
+        position
+            .legal_moves()
+            .into_iter()
+            .map(|mv| {
+                let mut next = position;
+                next.apply(mv).unwrap();
+                -minimax(next)
+            })
+            .max()
+            .unwrap()
+    }
+
+    // Positions from random games with `empties` squares left.
+    fn random_positions(count: usize, empties: u8) -> Vec<Position> {
+        let mut rng = XorShift::new(0x9e37_79b9_7f4a_7c15);
+        let mut ret = Vec::new();
+        while ret.len() < count {
+            let mut position = Position::initial();
+            while !position.is_game_over() {
+                if position.board.empty_squares_count() == empties {
+                    ret.push(position);
+                    break;
+                }
+                let moves = position.legal_moves();
+                let mv = moves[rng.next_u64() as usize % moves.len()];
+                position.apply(mv).unwrap();
+            }
+        }
+        ret
+    }
 
     #[test]
     fn next_move() {
         let boards = [
-            Board {
-                dark: 0x6000100810120500,
-                light: 0x8efceff76f6d3a3f,
-            },
-            Board {
-                dark: 0xfc2eeeb28a8c2e3e,
-                light: 0x0311114d75735100,
-            },
+            Board::new(0x6000100810120500, 0x8efceff76f6d3a3f),
+            Board::new(0xfc2eeeb28a8c2e3e, 0x0311114d75735100),
         ];
         let next_moves = ["E8", "H1"];
         let e = Exhausive::new(100000);
         for (b, s) in boards.iter().zip(next_moves.iter()) {
-            assert_eq!(
-                e.next_move(b.clone(), Color::Dark).unwrap().to_string(),
-                s.to_string()
-            );
+            assert_eq!(e.next_move(*b, Color::Dark).to_string(), s.to_string());
+        }
+    }
+
+    #[test]
+    fn solve() {
+        for position in random_positions(30, 8) {
+            let expected = minimax(position);
+            let mut e = Exhausive::new(100000);
+            let (mv, score) = e.solve(position.board, position.color).unwrap();
+            assert_eq!(score, expected, "{:?}", position);
+            let mut next = position;
+            next.apply(mv).unwrap();
+            assert_eq!(-minimax(next), expected, "{:?} {}", position, mv);
+
+            e.set_mode(SolveMode::WinLossDraw);
+            let (_, score) = e.solve(position.board, position.color).unwrap();
+            assert_eq!(score, expected.signum(), "{:?}", position);
+        }
+    }
+
+    #[test]
+    fn threads() {
+        for position in random_positions(10, 8) {
+            let mut e = Exhausive::new(100000);
+            e.table = Arc::new(TranspositionTable::new(1));
+            e.set_threads(4);
+            let (mv, score) = e.solve(position.board, position.color).unwrap();
+            assert_eq!(score, minimax(position), "{:?}", position);
+            let mut next = position;
+            next.apply(mv).unwrap();
+            assert_eq!(-minimax(next), score, "{:?} {}", position, mv);
+
+            e.set_mode(SolveMode::WinLossDraw);
+            let (_, wld) = e.solve(position.board, position.color).unwrap();
+            assert_eq!(wld, score.signum(), "{:?}", position);
+        }
+    }
+
+    #[test]
+    fn next_move_in_time() {
+        let position = random_positions(1, 19)[0];
+        let e = Exhausive::new(400);
+        let mv = e.next_move(position.board, position.color);
+        assert!(mv.is_legal(position.board, position.color));
+        assert!(
+            e.now.elapsed() < Duration::from_millis(500),
+            "{:?}",
+            e.now.elapsed()
+        );
+    }
+
+    #[test]
+    fn search_info() {
+        let board = Board::new(0x6000100810120500, 0x8efceff76f6d3a3f);
+        let e = Exhausive::new(100000);
+        let mv = e.next_move(board, Color::Dark);
+        let info = e.last_info().unwrap();
+        assert_eq!(info.depth, board.empty_squares_count() as u32);
+        assert!(info.score > 0);
+        assert_eq!(info.pv[0], mv);
+
+        // the line is played out to the end and comes to the score.
+        let mut position = Position::new(board, Color::Dark);
+        for &mv in info.pv.iter() {
+            position.apply(mv).unwrap();
         }
+        assert!(position.is_game_over());
+        let score = match position.color {
+            Color::Dark => position.final_score(),
+            Color::Light => -position.final_score(),
+        };
+        assert_eq!(score as i16, info.score);
     }
 }
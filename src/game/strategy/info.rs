@@ -0,0 +1,73 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::game::moves::Move;
+
+// Called with the result of every finished search iteration.
+pub type InfoCallback = Arc<dyn Fn(&SearchInfo) + Send + Sync>;
+
+// What a search found so far. `score` is from the side to move's point of
+// view, and `pv` starts with its move.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SearchInfo {
+    pub depth: u32,
+    pub score: i16,
+    pub nodes: u64,
+    pub elapsed: Duration,
+    pub pv: Vec<Move>,
+}
+
+impl SearchInfo {
+    pub fn nodes_per_second(&self) -> u64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs == 0.0 {
+            return 0;
+        }
+        (self.nodes as f64 / secs) as u64
+    }
+}
+
+impl fmt::Display for SearchInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "depth {} score {} nodes {} nps {} time {}ms pv",
+            self.depth,
+            self.score,
+            self.nodes,
+            self.nodes_per_second(),
+            self.elapsed.as_millis()
+        )?;
+        for mv in self.pv.iter() {
+            write!(f, " {}", mv)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn display() {
+        let info = SearchInfo {
+            depth: 3,
+            score: -12,
+            nodes: 3000,
+            elapsed: Duration::from_millis(1500),
+            pv: vec![
+                Move::from_str("F5").unwrap(),
+                Move::Pass,
+                Move::from_str("D6").unwrap(),
+            ],
+        };
+        assert_eq!(info.nodes_per_second(), 2000);
+        assert_eq!(
+            info.to_string(),
+            "depth 3 score -12 nodes 3000 nps 2000 time 1500ms pv F5 PASS D6"
+        );
+    }
+}
@@ -1,100 +1,261 @@
 use std::cmp;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
 use std::time::{Duration, Instant};
 
 use crate::game::base::Color;
 use crate::game::board::Board;
-use crate::game::opening_db::{DARK_MOVES, LIGHT_MOVES};
+use crate::game::moves::Move;
+use crate::game::opening_db::book_move;
 use crate::game::square::Square;
+use crate::game::square_set::SquareSet;
+use crate::game::strategy::info::{InfoCallback, SearchInfo};
+use crate::game::strategy::transposition::{
+    position_key, shared_table, Bound, Entry, TranspositionTable,
+};
 use crate::game::strategy::Strategy;
 
 pub struct NegaScout {
     pub should_stop: AtomicBool,
     pub now: Instant,
     pub time_limit: Duration,
-    pub emergency_ret: Option<u8>,
+    pub emergency_ret: Option<Square>,
+    pub table: Arc<TranspositionTable>,
+    threads: usize,
+    nodes: AtomicU64,
+    info_callback: Option<InfoCallback>,
+    last_info: Mutex<Option<SearchInfo>>,
 }
 
 impl Strategy for NegaScout {
-    fn next_move(&self, board: Board, color: Color) -> Option<Square> {
-        let moves = match color {
-            Color::Dark => DARK_MOVES.read().unwrap(),
-            Color::Light => LIGHT_MOVES.read().unwrap(),
-        };
-        if let Some(m) = moves.get(&board) {
-            return Some(*m);
+    fn next_move(&self, board: Board, color: Color) -> Move {
+        if let Some(m) = book_move(board, color) {
+            return Move::Square(m);
         }
         let flippables = board.flippable_squares(color);
         if flippables == 0 {
-            return None;
+            return Move::Pass;
         }
-        let count = flippables.count_ones();
+        let moves = Self::order_moves(board, color, flippables);
 
-        let depth = if count < 4 {
-            9
-        } else if count < 8 {
-            8
-        } else {
-            6
-        };
-
-        let mut ret = self.emergency_ret;
-        let mut cur_max = -5000;
-
-        for cur_square in (0..64).filter(|&x| flippables & 1 << x != 0) {
-            if self.should_stop.load(Ordering::Relaxed) {
-                break;
+        // lazy SMP: helpers search the same position on their own and only
+        // share what they find through the table.
+        let helpers: Vec<NegaScout> = (1..self.threads).map(|_| self.helper()).collect();
+        thread::scope(|scope| {
+            for (i, helper) in helpers.iter().enumerate() {
+                let moves = moves.clone();
+                scope.spawn(move || helper.help(board, color, moves, i + 1));
             }
-            self.check_time_limit();
-            let score = self.nega_scout(board, cur_square, color, depth, -5000, 5000);
-            if cur_max < score {
-                cur_max = score;
-                ret = Some(cur_square);
-            } else {
-                ret = ret.or(Some(cur_square));
+            let ret = self.deepen(board, color, moves, &helpers);
+            for helper in helpers.iter() {
+                helper.should_stop.store(true, Ordering::Relaxed);
             }
-        }
-        ret.map(|x| Square::from_uint(x))
+            Move::Square(Square::from_uint(ret))
+        })
+    }
+
+    fn abort(&self) {
+        self.should_stop.store(true, Ordering::Relaxed);
+    }
+
+    fn last_info(&self) -> Option<SearchInfo> {
+        self.last_info.lock().unwrap().clone()
     }
 }
 
 impl NegaScout {
-    pub fn new(time_limit_millisec: u64, emergency_ret: Option<u8>) -> Self {
+    // About how many times longer an iteration takes than the one before.
+    const GROWTH_FACTOR: u32 = 4;
+
+    pub fn new(time_limit_millisec: u64, emergency_ret: Option<Square>) -> Self {
         Self {
             should_stop: AtomicBool::new(false),
             now: Instant::now(),
             time_limit: Duration::from_millis(time_limit_millisec),
             emergency_ret,
+            table: shared_table(),
+            threads: 1,
+            nodes: AtomicU64::new(0),
+            info_callback: None,
+            last_info: Mutex::new(None),
         }
     }
 
-    pub fn new_from_duration(duration: Duration, emergency_ret: Option<u8>) -> Self {
+    pub fn new_from_duration(duration: Duration, emergency_ret: Option<Square>) -> Self {
         Self {
             should_stop: AtomicBool::new(false),
             now: Instant::now(),
             time_limit: duration,
             emergency_ret,
+            table: shared_table(),
+            threads: 1,
+            nodes: AtomicU64::new(0),
+            info_callback: None,
+            last_info: Mutex::new(None),
+        }
+    }
+
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = cmp::max(threads, 1);
+    }
+
+    // Called with every finished iteration.
+    pub fn set_info_callback(&mut self, callback: InfoCallback) {
+        self.info_callback = Some(callback);
+    }
+
+    // Iterative deepening: only an iteration that finished in time counts.
+    fn deepen(&self, board: Board, color: Color, mut moves: Vec<u8>, helpers: &[NegaScout]) -> u8 {
+        let mut ret = self
+            .emergency_ret
+            .map(|s| s.to_uint())
+            .filter(|s| moves.contains(s))
+            .unwrap_or(moves[0]);
+        let max_depth = board.empty_squares_count() as i8 - 1;
+        for depth in 0..=max_depth {
+            let started = Instant::now();
+            match self.search_root(board, color, &mut moves, depth) {
+                Some((score, pv)) => {
+                    ret = moves[0];
+                    let nodes = helpers
+                        .iter()
+                        .chain(std::iter::once(self))
+                        .map(|search| search.nodes.load(Ordering::Relaxed))
+                        .sum();
+                    self.report(SearchInfo {
+                        depth: depth as u32 + 1,
+                        score,
+                        nodes,
+                        elapsed: self.now.elapsed(),
+                        pv: pv
+                            .into_iter()
+                            .map(|s| Square::from_uint(s).into())
+                            .collect(),
+                    });
+                }
+                None => break,
+            }
+            let remaining = self
+                .time_limit
+                .checked_sub(self.now.elapsed())
+                .unwrap_or_default();
+            if started.elapsed() * Self::GROWTH_FACTOR > remaining {
+                break;
+            }
+        }
+        ret
+    }
+
+    // A search sharing our table, left to run until we stop it.
+    fn helper(&self) -> NegaScout {
+        let mut ret = NegaScout::new_from_duration(Duration::from_secs(u64::MAX / 2), None);
+        ret.table = Arc::clone(&self.table);
+        ret
+    }
+
+    // Helper `index` starts from another root move and every other one a ply
+    // deeper, so that the helpers don't all search the same tree in step.
+    fn help(&self, board: Board, color: Color, mut moves: Vec<u8>, index: usize) {
+        let len = moves.len();
+        moves.rotate_left(index % len);
+        let max_depth = board.empty_squares_count() as i8 - 1;
+        for depth in (index % 2) as i8..=max_depth {
+            if self.search_root(board, color, &mut moves, depth).is_none() {
+                break;
+            }
         }
     }
 
+    fn report(&self, info: SearchInfo) {
+        if let Some(callback) = self.info_callback.as_ref() {
+            callback(&info);
+        }
+        *self.last_info.lock().unwrap() = Some(info);
+    }
+
+    // Searches every move in `moves` `depth` plies further and sorts them best
+    // first, returning the best score and line, or `None` if the search was
+    // stopped.
+    fn search_root(
+        &self,
+        board: Board,
+        color: Color,
+        moves: &mut [u8],
+        depth: i8,
+    ) -> Option<(i16, Vec<u8>)> {
+        let mut scores = Vec::with_capacity(moves.len());
+        let mut alpha = -5000;
+        let mut pv = Vec::new();
+        let mut child_pv = Vec::new();
+        for (i, &mv) in moves.iter().enumerate() {
+            let mut score = if i == 0 {
+                self.nega_scout(board, mv, color, depth, alpha, 5000, &mut child_pv)
+            } else {
+                self.nega_scout(board, mv, color, depth, alpha, alpha + 1, &mut child_pv)
+            };
+            if i > 0 && alpha < score && !self.should_stop.load(Ordering::Relaxed) {
+                score = self.nega_scout(board, mv, color, depth, score, 5000, &mut child_pv);
+            }
+            if self.should_stop.load(Ordering::Relaxed) {
+                return None;
+            }
+            if i == 0 || alpha < score {
+                pv.clear();
+                pv.push(mv);
+                pv.extend_from_slice(&child_pv);
+            }
+            alpha = cmp::max(alpha, score);
+            scores.push(score);
+        }
+        // stable, so the previous best stays first on a tie.
+        let mut scored: Vec<(u8, i16)> = moves.iter().copied().zip(scores).collect();
+        scored.sort_by_key(|&(_, score)| cmp::Reverse(score));
+        for (mv, (scored_move, _)) in moves.iter_mut().zip(scored) {
+            *mv = scored_move;
+        }
+        Some((alpha, pv))
+    }
+
+    // Scores every legal move of `color` with a search of the given depth.
+    pub fn score_moves(&self, board: Board, color: Color, depth: i8) -> Vec<(Square, i16)> {
+        let flippables = board.flippable_squares(color);
+        SquareSet(flippables)
+            .indices()
+            .map(|x| {
+                let score = self.nega_scout(board, x, color, depth, -5000, 5000, &mut Vec::new());
+                (Square::from_uint(x), score)
+            })
+            .collect()
+    }
+
     #[inline]
-    pub fn emergency_move(board: Board, color: Color) -> Option<u8> {
+    pub fn emergency_move(board: Board, color: Color) -> Option<Square> {
         let flippables = board.flippable_squares(color);
         //TODO: improve
-        (0..64)
-            .filter(|&s| flippables & 1 << s != 0)
+        SquareSet(flippables)
+            .indices()
             .max_by_key(|&x| board.score(x, color))
+            .map(Square::from_uint)
     }
 
+    // Score of `color` playing `next_move` on `board`, searched `depth` plies
+    // further. Fails soft: a result outside (alpha, beta) is only a bound.
+    // `pv` is set to the line after `next_move` the score came from, which is
+    // the expected one when the score is inside the window.
+    #[allow(clippy::too_many_arguments)]
     fn nega_scout(
         &self,
         board: Board,
         next_move: u8,
         color: Color,
         depth: i8,
-        mut alpha: i16,
-        mut beta: i16,
+        alpha: i16,
+        beta: i16,
+        pv: &mut Vec<u8>,
     ) -> i16 {
+        self.nodes.fetch_add(1, Ordering::Relaxed);
+        pv.clear();
         let next_board = board.flip(next_move, color);
         let opposite = color.opposite();
         let flippables = next_board.flippable_squares(opposite);
@@ -107,53 +268,230 @@ impl NegaScout {
             return board.score(next_move, color);
         }
 
-        let moves = Self::order_moves(next_board, opposite, flippables);
-
-        for (i, &mv) in moves.iter().enumerate() {
-            if self.should_stop.load(Ordering::Relaxed) {
-                return alpha;
+        // the replies are searched from the opponent's side, with the window
+        // negated.
+        let (mut alpha, mut beta) = (-beta, -alpha);
+        let key = position_key(next_board, opposite);
+        let entry = self.table.probe(key);
+        // only null windows take cut-offs, so that the PV is searched in full.
+        if let Some(entry) = entry.filter(|e| e.depth >= depth && alpha + 1 == beta) {
+            match entry.bound {
+                Bound::Exact => return -entry.score,
+                Bound::Lower => alpha = cmp::max(alpha, entry.score),
+                Bound::Upper => beta = cmp::min(beta, entry.score),
             }
+            if alpha >= beta {
+                return -entry.score;
+            }
+        }
+        let original_alpha = alpha;
 
-            let score = -self.nega_scout(next_board, mv, opposite, depth - 1, -beta, -alpha);
+        let mut moves = Self::order_moves(next_board, opposite, flippables);
+        if let Some(best) = entry.and_then(|e| e.best_move) {
+            if let Some(i) = moves.iter().position(|&mv| mv == best) {
+                moves[..=i].rotate_right(1);
+            }
+        }
 
-            self.check_time_limit();
+        let mut best_score = -5000;
+        let mut best_move = moves[0];
+        let mut child_pv = Vec::new();
+        for (i, &mv) in moves.iter().enumerate() {
+            let mut score = if i == 0 {
+                self.nega_scout(
+                    next_board,
+                    mv,
+                    opposite,
+                    depth - 1,
+                    alpha,
+                    beta,
+                    &mut child_pv,
+                )
+            } else {
+                self.nega_scout(
+                    next_board,
+                    mv,
+                    opposite,
+                    depth - 1,
+                    alpha,
+                    alpha + 1,
+                    &mut child_pv,
+                )
+            };
             if self.should_stop.load(Ordering::Relaxed) {
-                return alpha;
+                return -cmp::max(best_score, score);
             }
 
-            if alpha < score && score < beta && i > 1 && depth > 1 {
-                alpha = -self.nega_scout(next_board, mv, opposite, depth - 1, -beta, -score);
+            if i > 0 && alpha < score && score < beta && depth > 1 {
+                score = self.nega_scout(
+                    next_board,
+                    mv,
+                    opposite,
+                    depth - 1,
+                    score,
+                    beta,
+                    &mut child_pv,
+                );
             }
 
+            if best_score < score {
+                best_score = score;
+                best_move = mv;
+                pv.clear();
+                pv.push(mv);
+                pv.extend_from_slice(&child_pv);
+            }
             alpha = cmp::max(alpha, score);
 
             // beta cut-off
             if alpha >= beta {
-                return alpha;
+                break;
             }
-            beta = alpha + 1;
-            self.check_time_limit();
         }
-        alpha
+
+        if !self.should_stop.load(Ordering::Relaxed) {
+            let bound = if best_score <= original_alpha {
+                Bound::Upper
+            } else if best_score >= beta {
+                Bound::Lower
+            } else {
+                Bound::Exact
+            };
+            self.table.store(
+                key,
+                Entry {
+                    depth,
+                    bound,
+                    score: best_score,
+                    best_move: Some(best_move),
+                },
+            );
+        }
+        -best_score
     }
 
     #[inline]
     fn check_time_limit(&self) {
-        if self.now.elapsed() > self.time_limit {
-            println!("Timeout! Aborting.");
-            self.should_stop.store(true, Ordering::Relaxed);
+        if self.now.elapsed() > self.time_limit && !self.should_stop.swap(true, Ordering::Relaxed) {
+            eprintln!("Timeout! Aborting.");
         }
     }
 
     #[inline]
     fn order_moves(board: Board, color: Color, flippables: u64) -> Vec<u8> {
-        let mut flippables: Vec<u8> = (0..64).filter(|&s| flippables & 1 << s != 0).collect();
+        let mut flippables: Vec<u8> = SquareSet(flippables).indices().collect();
         // using nega_scout is maybe too slow?
-        flippables.sort_by(|x, y| {
-            let x = board.score(*x, color);
-            let y = board.score(*y, color);
-            x.partial_cmp(&y).unwrap()
-        });
+        // best first, so that the null windows of the rest mostly hold.
+        flippables.sort_by_key(|&x| cmp::Reverse(board.score(x, color)));
         flippables
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::position::Position;
+
+    // The same search without pruning or a table.
+    fn minimax(board: Board, next_move: u8, color: Color, depth: i8) -> i16 {
+        let next_board = board.flip(next_move, color);
+        let opposite = color.opposite();
+        let flippables = next_board.flippable_squares(opposite);
+        if depth == 0 || flippables == 0 {
+            return board.score(next_move, color);
+        }
+        -SquareSet(flippables)
+            .indices()
+            .map(|mv| minimax(next_board, mv, opposite, depth - 1))
+            .max()
+            .unwrap()
+    }
+
+    #[test]
+    fn score_moves() {
+        let boards = [
+            (Board::initial(), Color::Dark),
+            (
+                Board::new(0x0000_0018_3c10_0000, 0x0000_2c20_0008_0000),
+                Color::Light,
+            ),
+            (
+                Board::new(0x0010_1c1c_0800_0000, 0x0000_0020_1638_0400),
+                Color::Dark,
+            ),
+        ];
+        for &(board, color) in boards.iter() {
+            let search = NegaScout {
+                table: Arc::new(TranspositionTable::new(1)),
+                ..NegaScout::new(u64::MAX / 2, None)
+            };
+            for depth in 0..4 {
+                for (square, score) in search.score_moves(board, color, depth) {
+                    let expected = minimax(board, square.to_uint(), color, depth);
+                    assert_eq!(score, expected, "{:?} {} {}", board, square, depth);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn next_move_in_time() {
+        let board = Board::new(0x0010_1c1c_0800_0000, 0x0000_0020_1638_0400);
+        let search = NegaScout::new(200, None);
+        let mv = search.next_move(board, Color::Dark);
+        assert!(mv.is_legal(board, Color::Dark));
+        assert!(search.now.elapsed() < Duration::from_secs(2));
+
+        // nothing finished: the emergency move is played.
+        let emergency = SquareSet(board.flippable_squares(Color::Dark))
+            .iter()
+            .last()
+            .unwrap();
+        let search = NegaScout::new(0, Some(emergency));
+        search.abort();
+        assert_eq!(
+            search.next_move(board, Color::Dark),
+            Move::Square(emergency)
+        );
+    }
+
+    #[test]
+    fn search_info() {
+        let board = Board::new(0x0010_1c1c_0800_0000, 0x0000_0020_1638_0400);
+        let depths = Arc::new(Mutex::new(Vec::new()));
+        let mut search = NegaScout::new(300, None);
+        let seen = Arc::clone(&depths);
+        search.set_info_callback(Arc::new(move |info| seen.lock().unwrap().push(info.depth)));
+        let mv = search.next_move(board, Color::Dark);
+
+        let depths = depths.lock().unwrap();
+        assert_eq!(*depths, (1..=depths.len() as u32).collect::<Vec<_>>());
+        let info = search.last_info().unwrap();
+        assert_eq!(info.depth, *depths.last().unwrap());
+        assert_eq!(info.pv[0], mv);
+        let mut position = Position::new(board, Color::Dark);
+        for &mv in info.pv.iter() {
+            position.apply(mv).unwrap();
+        }
+        // the line only stops short where the side to move can't play.
+        if info.pv.len() as u32 != info.depth {
+            assert!(
+                matches!(position.legal_moves()[..], [] | [Move::Pass]),
+                "{}",
+                info
+            );
+        }
+    }
+
+    #[test]
+    fn threads() {
+        let board = Board::new(0x0010_1c1c_0800_0000, 0x0000_0020_1638_0400);
+        let mut search = NegaScout::new(300, None);
+        search.table = Arc::new(TranspositionTable::new(1));
+        search.set_threads(3);
+        let mv = search.next_move(board, Color::Dark);
+        assert!(mv.is_legal(board, Color::Dark));
+        assert_eq!(search.last_info().unwrap().pv[0], mv);
+        assert!(search.now.elapsed() < Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,227 @@
+use lazy_static::lazy_static;
+use std::mem;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+use crate::game::base::Color;
+use crate::game::board::Board;
+
+pub const DEFAULT_SIZE_MB: usize = 16;
+
+// Mixed into the board key when light is to move, as `Board::key` only covers
+// the discs.
+const LIGHT_TO_MOVE_KEY: u64 = 0x9d39_247e_3377_6d41;
+
+// Endgame solvers store final disc differentials rather than evaluations, and
+// their entries are kept apart with a key of their own.
+const ENDGAME_KEY: u64 = 0xf2a6_1c9b_84e3_5d07;
+
+lazy_static! {
+    static ref SHARED_TABLE: RwLock<Arc<TranspositionTable>> =
+        RwLock::new(Arc::new(TranspositionTable::new(DEFAULT_SIZE_MB)));
+}
+
+// The table searches use unless they are given their own.
+pub fn shared_table() -> Arc<TranspositionTable> {
+    Arc::clone(&SHARED_TABLE.read().unwrap())
+}
+
+// Replaces the shared table with an empty one of `size_mb` megabytes.
+// Searches already running keep the old one.
+pub fn resize_shared_table(size_mb: usize) {
+    *SHARED_TABLE.write().unwrap() = Arc::new(TranspositionTable::new(size_mb));
+}
+
+#[inline]
+pub fn position_key(board: Board, color: Color) -> u64 {
+    match color {
+        Color::Dark => board.key(),
+        Color::Light => board.key() ^ LIGHT_TO_MOVE_KEY,
+    }
+}
+
+#[inline]
+pub fn endgame_key(board: Board, color: Color) -> u64 {
+    position_key(board, color) ^ ENDGAME_KEY
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Bound {
+    Exact,
+    // the score is at least the stored one.
+    Lower,
+    // the score is at most the stored one.
+    Upper,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Entry {
+    pub depth: i8,
+    pub bound: Bound,
+    pub score: i16,
+    pub best_move: Option<u8>,
+}
+
+impl Entry {
+    const NO_MOVE: u64 = 0xff;
+
+    fn pack(self) -> u64 {
+        let bound = match self.bound {
+            Bound::Exact => 1,
+            Bound::Lower => 2,
+            Bound::Upper => 3,
+        };
+        let best_move = self.best_move.map_or(Self::NO_MOVE, u64::from);
+        u64::from(self.score as u16)
+            | u64::from(self.depth as u8) << 16
+            | bound << 24
+            | best_move << 32
+    }
+
+    fn unpack(data: u64) -> Option<Self> {
+        let bound = match data >> 24 & 0xff {
+            1 => Bound::Exact,
+            2 => Bound::Lower,
+            3 => Bound::Upper,
+            _ => return None,
+        };
+        let best_move = data >> 32 & 0xff;
+        Some(Self {
+            depth: (data >> 16) as u8 as i8,
+            bound,
+            score: data as u16 as i16,
+            best_move: if best_move == Self::NO_MOVE {
+                None
+            } else {
+                Some(best_move as u8)
+            },
+        })
+    }
+}
+
+// One entry per slot, indexed by the low bits of the key. The key is stored
+// xored with the data, so a slot torn by two threads writing at once reads as
+// a miss instead of a wrong entry and no lock is needed.
+struct Slot {
+    check: AtomicU64,
+    data: AtomicU64,
+}
+
+pub struct TranspositionTable {
+    slots: Vec<Slot>,
+    mask: usize,
+}
+
+impl TranspositionTable {
+    // The slot count is the largest power of two fitting in `size_mb`, and at
+    // least one.
+    pub fn new(size_mb: usize) -> Self {
+        let count = size_mb * 1024 * 1024 / mem::size_of::<Slot>();
+        let count = if count == 0 {
+            1
+        } else {
+            1 << (usize::BITS - 1 - count.leading_zeros())
+        };
+        let slots = (0..count)
+            .map(|_| Slot {
+                check: AtomicU64::new(0),
+                data: AtomicU64::new(0),
+            })
+            .collect();
+        Self {
+            slots,
+            mask: count - 1,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    pub fn probe(&self, key: u64) -> Option<Entry> {
+        let slot = &self.slots[key as usize & self.mask];
+        let data = slot.data.load(Ordering::Relaxed);
+        if slot.check.load(Ordering::Relaxed) ^ data != key {
+            return None;
+        }
+        Entry::unpack(data)
+    }
+
+    // Keeps a deeper entry of the same position over a shallower one; any
+    // other position is replaced.
+    pub fn store(&self, key: u64, entry: Entry) {
+        let slot = &self.slots[key as usize & self.mask];
+        let old = slot.data.load(Ordering::Relaxed);
+        if slot.check.load(Ordering::Relaxed) ^ old == key {
+            if let Some(old) = Entry::unpack(old) {
+                if old.depth > entry.depth {
+                    return;
+                }
+            }
+        }
+        let data = entry.pack();
+        slot.check.store(key ^ data, Ordering::Relaxed);
+        slot.data.store(data, Ordering::Relaxed);
+    }
+
+    pub fn clear(&self) {
+        for slot in self.slots.iter() {
+            slot.check.store(0, Ordering::Relaxed);
+            slot.data.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn size() {
+        assert_eq!(TranspositionTable::new(0).len(), 1);
+        assert_eq!(TranspositionTable::new(1).len(), 1 << 16);
+        assert_eq!(TranspositionTable::new(3).len(), 1 << 17);
+    }
+
+    #[test]
+    fn store_and_probe() {
+        let table = TranspositionTable::new(1);
+        let entry = Entry {
+            depth: 6,
+            bound: Bound::Lower,
+            score: -1234,
+            best_move: Some(63),
+        };
+        let key = position_key(Board::initial(), Color::Dark);
+        assert_eq!(table.probe(key), None);
+        table.store(key, entry);
+        assert_eq!(table.probe(key), Some(entry));
+        assert_eq!(
+            table.probe(position_key(Board::initial(), Color::Light)),
+            None
+        );
+
+        // a shallower result doesn't replace a deeper one.
+        let shallow = Entry {
+            depth: 2,
+            bound: Bound::Exact,
+            score: 10,
+            best_move: None,
+        };
+        table.store(key, shallow);
+        assert_eq!(table.probe(key), Some(entry));
+        let deep = Entry {
+            depth: 7,
+            ..shallow
+        };
+        table.store(key, deep);
+        assert_eq!(table.probe(key), Some(deep));
+
+        table.clear();
+        assert_eq!(table.probe(key), None);
+    }
+}
@@ -0,0 +1,136 @@
+// The eight symmetries of the board (the dihedral group of the square).
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Transform {
+    Identity,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    // upside down, same as `Board::mirror`
+    Mirror,
+    // left to right
+    FlipHorizontal,
+    // along the A1-H8 diagonal
+    FlipDiagonal,
+    // along the H1-A8 diagonal
+    FlipAntiDiagonal,
+}
+
+impl Transform {
+    pub const ALL: [Transform; 8] = [
+        Transform::Identity,
+        Transform::Rotate90,
+        Transform::Rotate180,
+        Transform::Rotate270,
+        Transform::Mirror,
+        Transform::FlipHorizontal,
+        Transform::FlipDiagonal,
+        Transform::FlipAntiDiagonal,
+    ];
+
+    pub fn inverse(self) -> Self {
+        match self {
+            Transform::Rotate90 => Transform::Rotate270,
+            Transform::Rotate270 => Transform::Rotate90,
+            t => t,
+        }
+    }
+
+    // Maps the square at (x, y) to its image.
+    pub fn apply_xy(self, x: u8, y: u8) -> (u8, u8) {
+        match self {
+            Transform::Identity => (x, y),
+            Transform::Rotate90 => (7 - y, x),
+            Transform::Rotate180 => (7 - x, 7 - y),
+            Transform::Rotate270 => (y, 7 - x),
+            Transform::Mirror => (x, 7 - y),
+            Transform::FlipHorizontal => (7 - x, y),
+            Transform::FlipDiagonal => (y, x),
+            Transform::FlipAntiDiagonal => (7 - y, 7 - x),
+        }
+    }
+
+    #[inline]
+    pub fn apply_bits(self, bits: u64) -> u64 {
+        match self {
+            Transform::Identity => bits,
+            Transform::Rotate90 => flip_horizontal(flip_diagonal(bits)),
+            Transform::Rotate180 => bits.reverse_bits(),
+            Transform::Rotate270 => flip_vertical(flip_diagonal(bits)),
+            Transform::Mirror => flip_vertical(bits),
+            Transform::FlipHorizontal => flip_horizontal(bits),
+            Transform::FlipDiagonal => flip_diagonal(bits),
+            Transform::FlipAntiDiagonal => flip_anti_diagonal(bits),
+        }
+    }
+}
+
+#[inline]
+fn flip_vertical(bits: u64) -> u64 {
+    bits.swap_bytes()
+}
+
+#[inline]
+fn flip_horizontal(mut bits: u64) -> u64 {
+    bits = ((bits >> 1) & 0x5555555555555555) | ((bits & 0x5555555555555555) << 1);
+    bits = ((bits >> 2) & 0x3333333333333333) | ((bits & 0x3333333333333333) << 2);
+    ((bits >> 4) & 0x0f0f0f0f0f0f0f0f) | ((bits & 0x0f0f0f0f0f0f0f0f) << 4)
+}
+
+#[inline]
+fn flip_diagonal(mut bits: u64) -> u64 {
+    let mut tmp = 0x0f0f0f0f00000000 & (bits ^ (bits << 28));
+    bits ^= tmp ^ (tmp >> 28);
+    tmp = 0x3333000033330000 & (bits ^ (bits << 14));
+    bits ^= tmp ^ (tmp >> 14);
+    tmp = 0x5500550055005500 & (bits ^ (bits << 7));
+    bits ^ tmp ^ (tmp >> 7)
+}
+
+#[inline]
+fn flip_anti_diagonal(mut bits: u64) -> u64 {
+    let mut tmp = bits ^ (bits << 36);
+    bits ^= 0xf0f0f0f00f0f0f0f & (tmp ^ (bits >> 36));
+    tmp = 0xcccc0000cccc0000 & (bits ^ (bits << 18));
+    bits ^= tmp ^ (tmp >> 18);
+    tmp = 0xaa00aa00aa00aa00 & (bits ^ (bits << 9));
+    bits ^ tmp ^ (tmp >> 9)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::base::Color;
+    use crate::game::board::Board;
+    use crate::game::square::Square;
+    use std::str::FromStr;
+
+    #[test]
+    fn squares_and_bits_agree() {
+        for &t in Transform::ALL.iter() {
+            for i in 0..64 {
+                let square = Square::from_uint(i).transform(t);
+                assert_eq!(t.apply_bits(1 << i), 1 << square.to_uint(), "{:?}", t);
+                assert_eq!(square.transform(t.inverse()), Square::from_uint(i));
+            }
+        }
+    }
+
+    #[test]
+    fn matches_rotations() {
+        let board = Board::new(0x6000100810120500, 0x8efceff76f6d3a3f);
+        assert_eq!(board.transform(Transform::Rotate90), board.rotate_90());
+        assert_eq!(board.transform(Transform::Rotate180), board.rotate_180());
+        assert_eq!(board.transform(Transform::Rotate270), board.rotate_270());
+        assert_eq!(board.transform(Transform::Mirror), board.mirror());
+    }
+
+    #[test]
+    fn canonical() {
+        let board = Board::initial().flip(Square::from_str("D3").unwrap().to_uint(), Color::Dark);
+        let (canonical, t) = board.canonical();
+        assert_eq!(board.transform(t), canonical);
+        for &u in Transform::ALL.iter() {
+            assert_eq!(board.transform(u).canonical().0, canonical);
+        }
+    }
+}
@@ -2,3 +2,21 @@
 pub fn clz(x: u64) -> u8 {
     x.leading_zeros() as u8 % 64
 }
+
+// Repeatable pseudo-random numbers for tests.
+#[cfg(test)]
+pub struct XorShift(u64);
+
+#[cfg(test)]
+impl XorShift {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+use std::hash::{BuildHasherDefault, Hasher};
+
+use crate::game::base::Color;
+use crate::game::board::Board;
+use crate::game::square_set::SquareSet;
+
+// One random key per (color, square), generated at compile time so that keys
+// are stable between runs.
+const KEYS: [[u64; 64]; 2] = generate_keys();
+
+const fn generate_keys() -> [[u64; 64]; 2] {
+    let mut keys = [[0u64; 64]; 2];
+    // splitmix64
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let mut i = 0;
+    while i < 128 {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        keys[i / 64][i % 64] = z ^ (z >> 31);
+        i += 1;
+    }
+    keys
+}
+
+#[inline]
+pub fn square_key(square: u8, color: Color) -> u64 {
+    match color {
+        Color::Dark => KEYS[0][square as usize],
+        Color::Light => KEYS[1][square as usize],
+    }
+}
+
+// Key difference of turning the discs in `squares` over.
+#[inline]
+pub fn flip_key(squares: u64) -> u64 {
+    SquareSet(squares).indices().fold(0, |key, square| {
+        key ^ KEYS[0][square as usize] ^ KEYS[1][square as usize]
+    })
+}
+
+pub fn compute_key(dark: u64, light: u64) -> u64 {
+    let dark_key = SquareSet(dark)
+        .indices()
+        .fold(0, |key, square| key ^ square_key(square, Color::Dark));
+    SquareSet(light).indices().fold(dark_key, |key, square| {
+        key ^ square_key(square, Color::Light)
+    })
+}
+
+// Boards already hash to their Zobrist key, so the hasher only has to pass it
+// through. Anything else hashed alongside it (e.g. a color) is mixed in.
+#[derive(Default)]
+pub struct ZobristHasher(u64);
+
+impl Hasher for ZobristHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.write_u8(b);
+        }
+    }
+
+    fn write_u8(&mut self, n: u8) {
+        self.mix(n as u64);
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.0 ^= n;
+    }
+
+    fn write_usize(&mut self, n: usize) {
+        self.mix(n as u64);
+    }
+
+    fn write_isize(&mut self, n: isize) {
+        self.mix(n as u64);
+    }
+}
+
+impl ZobristHasher {
+    fn mix(&mut self, n: u64) {
+        self.0 = self.0.rotate_left(5) ^ n.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    }
+}
+
+pub type ZobristMap<K, V> = HashMap<K, V, BuildHasherDefault<ZobristHasher>>;
+pub type BoardMap<V> = ZobristMap<Board, V>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_distinct() {
+        let mut keys: Vec<u64> = KEYS.iter().flatten().cloned().collect();
+        keys.sort_unstable();
+        keys.dedup();
+        assert_eq!(keys.len(), 128);
+    }
+}
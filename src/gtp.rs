@@ -0,0 +1,307 @@
+use std::io::{BufRead, Write};
+use std::str::{FromStr, SplitWhitespace};
+
+use crate::error::{Error, Result};
+use crate::game::base::Color;
+use crate::game::board::Board;
+use crate::game::moves::Move;
+use crate::game::position::Position;
+use crate::game::strategy::{Exhausive, NegaScout, Strategy};
+
+const ENGINE_NAME: &str = "reversi_rs";
+const ENGINE_VERSION: &str = env!("CARGO_PKG_VERSION");
+const ENDGAME_BORDER: u8 = 24;
+// Thinking time per move when no time settings were given.
+const DEFAULT_MOVE_TIME_MS: u64 = 5000;
+
+const COMMANDS: [&str; 16] = [
+    "protocol_version",
+    "name",
+    "version",
+    "known_command",
+    "list_commands",
+    "quit",
+    "boardsize",
+    "clear_board",
+    "play",
+    "genmove",
+    "undo",
+    "showboard",
+    "final_score",
+    "time_settings",
+    "time_left",
+    "komi",
+];
+
+// Line based command interface modeled on the Go Text Protocol.
+pub struct GtpEngine {
+    board: Board,
+    history: Vec<Board>,
+    main_time_ms: u64,
+    remaining_time_ms: [Option<u64>; 2],
+}
+
+impl Default for GtpEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GtpEngine {
+    pub fn new() -> Self {
+        Self {
+            board: Board::initial(),
+            history: Vec::new(),
+            main_time_ms: 0,
+            remaining_time_ms: [None, None],
+        }
+    }
+
+    pub fn run<R: BufRead, W: Write>(&mut self, input: R, mut output: W) -> Result<()> {
+        for line in input.lines() {
+            let line = line?;
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let mut split = line.split_whitespace();
+            let first = split.next().unwrap_or("");
+            let (id, cmd) = match first.parse::<u32>() {
+                Ok(id) => (Some(id), split.next().unwrap_or("")),
+                Err(_) => (None, first),
+            };
+            let id = id.map(|id| id.to_string()).unwrap_or_default();
+            match self.handle_command(cmd, &mut split) {
+                Ok(response) => writeln!(output, "={} {}\n", id, response)?,
+                Err(e) => writeln!(output, "?{} {}\n", id, failure_message(&e))?,
+            }
+            output.flush()?;
+            if cmd == "quit" {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_command(&mut self, cmd: &str, args: &mut SplitWhitespace) -> Result<String> {
+        match cmd {
+            "protocol_version" => Ok("2".to_string()),
+            "name" => Ok(ENGINE_NAME.to_string()),
+            "version" => Ok(ENGINE_VERSION.to_string()),
+            "known_command" => Ok(COMMANDS.contains(&args.next().unwrap_or("")).to_string()),
+            "list_commands" => Ok(COMMANDS.join("\n")),
+            "quit" | "komi" => Ok(String::new()),
+            "boardsize" => match args.next() {
+                Some("8") => Ok(String::new()),
+                _ => Err(malformed("unacceptable size")),
+            },
+            "clear_board" => {
+                self.board = Board::initial();
+                self.history.clear();
+                Ok(String::new())
+            }
+            "play" => {
+                let color = parse_color(args.next())?;
+                let mv = parse_vertex(args.next())?;
+                self.play(color, mv).map(|_| String::new())
+            }
+            "genmove" => {
+                let color = parse_color(args.next())?;
+                let mv = self.generate_move(color);
+                self.play(color, mv)?;
+                Ok(format_vertex(mv))
+            }
+            "undo" => match self.history.pop() {
+                Some(board) => {
+                    self.board = board;
+                    Ok(String::new())
+                }
+                None => Err(malformed("cannot undo")),
+            },
+            "showboard" => Ok(format!("\n{}", self.board.diagram().trim_end())),
+            "final_score" => Ok(self.final_score()),
+            "time_settings" => {
+                let main_time = parse_number(args.next())?;
+                self.main_time_ms = main_time * 1000;
+                self.remaining_time_ms = [None, None];
+                Ok(String::new())
+            }
+            "time_left" => {
+                let color = parse_color(args.next())?;
+                let time = parse_number(args.next())?;
+                self.remaining_time_ms[color_index(color)] = Some(time * 1000);
+                Ok(String::new())
+            }
+            _ => Err(Error::UnknownCommand(cmd.to_string())),
+        }
+    }
+
+    fn play(&mut self, color: Color, mv: Move) -> Result<()> {
+        if !mv.is_legal(self.board, color) {
+            return Err(Error::IllegalMove(mv));
+        }
+        let next = match mv {
+            Move::Square(square) => self.board.flip(square.to_uint(), color),
+            Move::Pass => self.board,
+        };
+        self.history.push(self.board);
+        self.board = next;
+        Ok(())
+    }
+
+    fn generate_move(&self, color: Color) -> Move {
+        if self.board.flippable_squares(color) == 0 {
+            return Move::Pass;
+        }
+        let time = self.move_time_ms(color);
+        let strategy: Box<dyn Strategy> = if self.board.empty_squares_count() < ENDGAME_BORDER {
+            Box::new(Exhausive::new(time))
+        } else {
+            Box::new(NegaScout::new(
+                time,
+                NegaScout::emergency_move(self.board, color),
+            ))
+        };
+        strategy.next_move(self.board, color)
+    }
+
+    // Spreads the remaining time over the moves we still have to make.
+    fn move_time_ms(&self, color: Color) -> u64 {
+        let remaining = self.remaining_time_ms[color_index(color)].unwrap_or(self.main_time_ms);
+        if remaining == 0 {
+            return DEFAULT_MOVE_TIME_MS;
+        }
+        let moves_left = (self.board.empty_squares_count() as u64).div_ceil(2);
+        remaining / moves_left.max(1)
+    }
+
+    fn final_score(&self) -> String {
+        let score = Position::new(self.board, Color::Dark).final_score();
+        if score > 0 {
+            format!("B+{}", score)
+        } else if score < 0 {
+            format!("W+{}", -score)
+        } else {
+            "0".to_string()
+        }
+    }
+}
+
+fn color_index(color: Color) -> usize {
+    match color {
+        Color::Dark => 0,
+        Color::Light => 1,
+    }
+}
+
+// GTP's own wording for the failures controllers look for.
+fn failure_message(e: &Error) -> String {
+    match e {
+        Error::IllegalMove(_) => "illegal move".to_string(),
+        Error::UnknownCommand(_) => "unknown command".to_string(),
+        Error::BadSquare(_) => "invalid vertex".to_string(),
+        Error::MalformedMessage(msg) => msg.clone(),
+        e => e.to_string(),
+    }
+}
+
+fn malformed(msg: &str) -> Error {
+    Error::MalformedMessage(msg.to_string())
+}
+
+fn parse_color(s: Option<&str>) -> Result<Color> {
+    match s.map(|s| s.to_lowercase()).as_deref() {
+        Some("b") | Some("black") => Ok(Color::Dark),
+        Some("w") | Some("white") => Ok(Color::Light),
+        _ => Err(malformed("invalid color")),
+    }
+}
+
+fn parse_vertex(s: Option<&str>) -> Result<Move> {
+    let s = s.unwrap_or("");
+    Move::from_str(s).map_err(|_| Error::BadSquare(s.to_string()))
+}
+
+// GTP spells a pass in lower case.
+fn format_vertex(mv: Move) -> String {
+    match mv {
+        Move::Square(square) => square.to_string(),
+        Move::Pass => "pass".to_string(),
+    }
+}
+
+fn parse_number(s: Option<&str>) -> Result<u64> {
+    s.and_then(|s| s.parse().ok())
+        .ok_or_else(|| malformed("syntax error"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::square::Square;
+    use std::io::Cursor;
+
+    fn run(commands: &str) -> Vec<String> {
+        let mut output = Vec::new();
+        GtpEngine::new()
+            .run(Cursor::new(commands.to_string()), &mut output)
+            .unwrap();
+        String::from_utf8(output)
+            .unwrap()
+            .split("\n\n")
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn play_and_undo() {
+        let responses = run("1 play b f5\nplay w f5\nplay b pass\nundo\nundo\nundo\nfinal_score\n");
+        assert_eq!(
+            responses,
+            vec![
+                "=1 ",
+                "? illegal move",
+                "? illegal move",
+                "= ",
+                "? cannot undo",
+                "? cannot undo",
+                "= 0"
+            ]
+        );
+    }
+
+    #[test]
+    fn genmove() {
+        let responses = run("time_settings 1 0 0\nplay b f5\ngenmove w\nshowboard\n");
+        assert_eq!(responses.len(), 4);
+        let square = Square::from_str(&responses[2][2..]).unwrap();
+        let board = Board::initial().flip(Square::from_str("F5").unwrap().to_uint(), Color::Dark);
+        assert_ne!(
+            board.flippable_squares(Color::Light) & 1 << square.to_uint(),
+            0
+        );
+    }
+
+    #[test]
+    fn final_score() {
+        let mut engine = GtpEngine::new();
+        engine.board = Board::new(0xffff_ffff_ffff_0000, 0xff);
+        assert_eq!(engine.final_score(), "B+48");
+    }
+
+    #[test]
+    fn failures() {
+        let responses = run("foo\nplay x f5\nplay b z9\nboardsize 19\ntime_left b\n");
+        assert_eq!(
+            responses,
+            vec![
+                "? unknown command",
+                "? invalid color",
+                "? invalid vertex",
+                "? unacceptable size",
+                "? syntax error"
+            ]
+        );
+    }
+}
@@ -0,0 +1,25 @@
+//! Reversi engine used by the contest client.
+//!
+//! The crate exposes the board representation and rules (`game::board`,
+//! `game::square`, `game::moves`), the search strategies
+//! (`game::strategy`), the opening book (`game::opening_db`), the contest
+//! protocol client (`cli`, `message`, `transport`), a reference game server
+//! (`server`), the NBoard and GTP-style engine front ends (`nboard`,
+//! `gtp`) and board rendering (`render`). The `reversi_rs` binary is a thin
+//! consumer of this API.
+
+pub mod cli;
+pub mod error;
+pub mod game;
+pub mod gtp;
+pub mod message;
+pub mod nboard;
+pub mod render;
+pub mod server;
+pub mod transport;
+
+pub use crate::game::base::{Color, Game, GameResult};
+pub use crate::game::board::Board;
+pub use crate::game::moves::Move;
+pub use crate::game::square::Square;
+pub use crate::game::strategy::{Exhausive, Naive, NegaScout, Strategy};
@@ -1,17 +1,32 @@
 extern crate clap;
 
-mod cli;
-mod game;
-mod message;
+use clap::{AppSettings, Arg, ArgMatches, SubCommand};
+use std::io;
+use std::process;
+use std::time::{Duration, Instant};
 
-use clap::Arg;
-
-use crate::game::base::Game;
-use crate::game::opening_db::load_from_file;
+use reversi_rs::cli::{Client, ReconnectPolicy};
+use reversi_rs::error::{Error, Result};
+use reversi_rs::game::base::{Color, Game};
+use reversi_rs::game::board::Board;
+use reversi_rs::game::movegen;
+use reversi_rs::game::opening_db::load_from_file;
+use reversi_rs::game::perft::perft;
+use reversi_rs::game::strategy::transposition::resize_shared_table;
+use reversi_rs::gtp::GtpEngine;
+use reversi_rs::nboard::NBoardEngine;
+use reversi_rs::render::BoardView;
+use reversi_rs::server::Server;
 
 const DEFAULT_PORT: &str = "3000";
 const DEFAULT_HOST: &str = "localhost";
 const DEFAULT_NAME: &str = "Joe";
+const DEFAULT_RECONNECT_ATTEMPTS: &str = "5";
+const DEFAULT_RECONNECT_INTERVAL_MS: &str = "1000";
+const DEFAULT_GAME_COUNT: &str = "1";
+const DEFAULT_TIME_MS: &str = "60000";
+const DEFAULT_PERFT_DEPTH: &str = "10";
+const DEFAULT_THREADS: &str = "1";
 
 fn main() {
     let matches = clap::App::new("Let's Reversi")
@@ -36,8 +51,152 @@ fn main() {
                 .help("Sets a player's name for this reversi match")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("reconnect")
+                .short("r")
+                .value_name("Attempts")
+                .help("Sets how many times to reconnect after the connection drops")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("reconnect-interval")
+                .long("reconnect-interval")
+                .value_name("Interval")
+                .help("Sets the wait between reconnect attempts in milliseconds")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("log")
+                .short("l")
+                .value_name("Log")
+                .help("Records every exchanged message to a session log file")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("check-end")
+                .long("check-end")
+                .help("Checks the disc counts reported at the end of a game against our board"),
+        )
+        .arg(
+            Arg::with_name("ponder")
+                .long("ponder")
+                .help("Searches on the opponent's time"),
+        )
+        .arg(
+            Arg::with_name("hash")
+                .long("hash")
+                .value_name("MB")
+                .help("Sets the size of the search's transposition table in megabytes")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("threads")
+                .long("threads")
+                .value_name("Threads")
+                .help("Sets the number of search threads")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("search-info")
+                .long("search-info")
+                .help("Prints depth, score, speed and expected line of every search iteration"),
+        )
+        .arg(
+            Arg::with_name("show-board")
+                .long("show-board")
+                .help("Draws the board in color after every move"),
+        )
+        .subcommand(
+            SubCommand::with_name("server")
+                .about("Runs a local game server for two clients")
+                .arg(
+                    Arg::with_name("port")
+                        .short("p")
+                        .value_name("Port")
+                        .help("Sets a port to listen on")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("games")
+                        .short("g")
+                        .value_name("Games")
+                        .help("Sets the number of games to play")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("time")
+                        .short("t")
+                        .value_name("Time")
+                        .help("Sets each player's time for a game in milliseconds")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("replay")
+                .about("Replays a recorded session log against the engine")
+                .arg(
+                    Arg::with_name("log")
+                        .value_name("Log")
+                        .help("Session log recorded with -l")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("nboard")
+                .about("Runs as an NBoard protocol engine over stdin/stdout"),
+        )
+        .subcommand(
+            SubCommand::with_name("gtp")
+                .about("Runs a GTP-style text command interface over stdin/stdout"),
+        )
+        .subcommand(
+            SubCommand::with_name("perft")
+                .about("Counts the leaf nodes of the game tree from the initial board")
+                .arg(
+                    Arg::with_name("depth")
+                        .short("d")
+                        .value_name("Depth")
+                        .help("Sets the deepest depth to count")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("render")
+                .about("Draws a board given as a board string and side to move")
+                .setting(AppSettings::AllowLeadingHyphen)
+                .arg(
+                    Arg::with_name("board")
+                        .value_name("Board")
+                        .help("64 squares of X, O or - followed by X or O to move")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("svg")
+                        .long("svg")
+                        .help("Writes SVG instead of drawing to the terminal"),
+                ),
+        )
         .get_matches();
 
+    if let Some(size) = matches.value_of("hash") {
+        resize_shared_table(size.parse().expect("Invalid hash size specified."));
+    }
+    let result = match matches.subcommand() {
+        ("server", Some(matches)) => run_server(matches),
+        ("replay", Some(matches)) => run_replay(matches),
+        ("nboard", Some(_)) => run_nboard(),
+        ("gtp", Some(_)) => run_gtp(),
+        ("perft", Some(matches)) => run_perft(matches),
+        ("render", Some(matches)) => run_render(matches),
+        _ => run_client(&matches),
+    };
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+fn run_client(matches: &ArgMatches) -> Result<()> {
     let host = matches.value_of("host").unwrap_or(DEFAULT_HOST);
     let port: u32 = matches
         .value_of("port")
@@ -45,10 +204,117 @@ fn main() {
         .parse()
         .expect("Invalid port specified.");
     let name = matches.value_of("name").unwrap_or(DEFAULT_NAME);
+    let reconnect_attempts: u32 = matches
+        .value_of("reconnect")
+        .unwrap_or(DEFAULT_RECONNECT_ATTEMPTS)
+        .parse()
+        .expect("Invalid reconnect attempts specified.");
+    let reconnect_interval: u64 = matches
+        .value_of("reconnect-interval")
+        .unwrap_or(DEFAULT_RECONNECT_INTERVAL_MS)
+        .parse()
+        .expect("Invalid reconnect interval specified.");
+    let threads: usize = matches
+        .value_of("threads")
+        .unwrap_or(DEFAULT_THREADS)
+        .parse()
+        .expect("Invalid thread count specified.");
+    let policy = ReconnectPolicy::new(
+        reconnect_attempts,
+        Duration::from_millis(reconnect_interval),
+    );
     println!("Loading opening db...");
     load_from_file();
     println!("Launching reversi...");
-    let game = Game::launch(host, port, name).unwrap();
-    game.main_loop();
+    let mut client = Client::new(host, port)?;
+    if let Some(path) = matches.value_of("log") {
+        client.record_session(path)?;
+    }
+    let mut game = Game::new(client, name)?;
+    game.set_reconnect_policy(policy);
+    game.set_check_end_counts(matches.is_present("check-end"));
+    game.set_pondering(matches.is_present("ponder"));
+    game.set_show_board(matches.is_present("show-board"));
+    game.set_show_search_info(matches.is_present("search-info"));
+    game.set_threads(threads);
+    game.main_loop()?;
     println!("Game Ended!");
+    Ok(())
+}
+
+fn run_server(matches: &ArgMatches) -> Result<()> {
+    let port: u32 = matches
+        .value_of("port")
+        .unwrap_or(DEFAULT_PORT)
+        .parse()
+        .expect("Invalid port specified.");
+    let games: u32 = matches
+        .value_of("games")
+        .unwrap_or(DEFAULT_GAME_COUNT)
+        .parse()
+        .expect("Invalid number of games specified.");
+    let time: i32 = matches
+        .value_of("time")
+        .unwrap_or(DEFAULT_TIME_MS)
+        .parse()
+        .expect("Invalid time specified.");
+    let server = Server::bind(&format!("0.0.0.0:{}", port), games, time)?;
+    println!("Waiting for players on port {}...", port);
+    server.run()?;
+    println!("Server Ended!");
+    Ok(())
+}
+
+fn run_replay(matches: &ArgMatches) -> Result<()> {
+    println!("Loading opening db...");
+    load_from_file();
+    println!("Replaying session...");
+    let mut game = Game::replay(matches.value_of("log").unwrap())?;
+    game.main_loop()?;
+    println!("Replay Ended!");
+    match game.replay_mismatches() {
+        0 => Ok(()),
+        count => Err(Error::ReplayMismatch(count)),
+    }
+}
+
+fn run_nboard() -> Result<()> {
+    let stdin = io::stdin();
+    NBoardEngine::new().run(stdin.lock(), io::stdout())
+}
+
+fn run_gtp() -> Result<()> {
+    let stdin = io::stdin();
+    GtpEngine::new().run(stdin.lock(), io::stdout())
+}
+
+fn run_perft(matches: &ArgMatches) -> Result<()> {
+    let depth: u32 = matches
+        .value_of("depth")
+        .unwrap_or(DEFAULT_PERFT_DEPTH)
+        .parse()
+        .expect("Invalid depth specified.");
+    println!("mobility: {}", movegen::mobility_backend());
+    for d in 1..=depth {
+        let now = Instant::now();
+        let count = perft(Board::initial(), Color::Dark, d);
+        println!("depth {:2}: {:12} ({:?})", d, count, now.elapsed());
+    }
+    Ok(())
+}
+
+fn run_render(matches: &ArgMatches) -> Result<()> {
+    let (board, color) =
+        Board::parse_obf(matches.value_of("board").unwrap()).expect("Invalid board specified.");
+    let view = BoardView {
+        board,
+        side_to_move: Some(color),
+        last_move: None,
+    };
+    if matches.is_present("svg") {
+        print!("{}", view.svg());
+    } else {
+        print!("{}", view.ansi());
+    }
+    Ok(())
 }
@@ -1,20 +1,53 @@
 use crate::game::base::{Color, GameResult};
-use crate::game::square::Square;
+use crate::game::moves::Move;
 
 // client
 pub fn open_message(name: &str) -> String {
-    vec!["OPEN", name].join(" ")
+    ["OPEN", name].join(" ")
 }
 
-pub fn move_message(s: Square) -> String {
-    vec!["MOVE", &s.to_string()].join(" ")
+pub fn move_message(mv: Move) -> String {
+    ["MOVE", &mv.to_string()].join(" ")
 }
 
-pub fn pass_message() -> String {
-    "MOVE PASS".to_string()
+pub enum ClientMessage {
+    Open { name: String },
+    Move { pos: Move },
 }
 
 // server
+pub fn start_message(color: Color, op_name: &str, remaining_time_ms: i32) -> String {
+    let color = match color {
+        Color::Dark => "BLACK",
+        Color::Light => "WHITE",
+    };
+    ["START", color, op_name, &remaining_time_ms.to_string()].join(" ")
+}
+
+pub fn end_message(result: GameResult, player_count: u8, op_count: u8, reason: &str) -> String {
+    let result = match result {
+        GameResult::Win => "WIN",
+        GameResult::Lose => "LOSE",
+        GameResult::Tie => "TIE",
+    };
+    [
+        "END",
+        result,
+        &player_count.to_string(),
+        &op_count.to_string(),
+        reason,
+    ]
+    .join(" ")
+}
+
+pub fn ack_message(remaining_time_ms: i32) -> String {
+    ["ACK", &remaining_time_ms.to_string()].join(" ")
+}
+
+pub fn bye_message(stat: &str) -> String {
+    ["BYE", stat].join(" ")
+}
+
 pub enum ServerMessage {
     Start {
         color: Color,
@@ -28,7 +61,7 @@ pub enum ServerMessage {
         reason: String,
     },
     Move {
-        pos: Option<Square>,
+        pos: Move,
     },
     Ack {
         remaining_time_ms: i32,
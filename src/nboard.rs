@@ -0,0 +1,329 @@
+use std::io::{BufRead, Write};
+use std::str::{FromStr, SplitWhitespace};
+use std::sync::atomic::Ordering;
+
+use crate::error::{Error, Result};
+use crate::game::base::Color;
+use crate::game::board::Board;
+use crate::game::moves::Move;
+use crate::game::square::Square;
+use crate::game::strategy::{Exhausive, NegaScout, Strategy};
+
+const ENGINE_NAME: &str = "reversi_rs";
+const DEFAULT_DEPTH: i8 = 6;
+// Exhausive needs a time limit; the GUI has no clock so give it plenty.
+const ENDGAME_TIME_LIMIT_MS: u64 = 60_000;
+// Midgame searches stop deepening after this long, so that the engine keeps
+// answering whatever depth the GUI asks for.
+const MIDGAME_TIME_LIMIT_MS: u64 = 10_000;
+
+// Engine side of the NBoard protocol, talking over any line based stream
+// (stdin/stdout when launched by the GUI).
+pub struct NBoardEngine {
+    board: Board,
+    color: Color,
+    depth: i8,
+}
+
+impl Default for NBoardEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NBoardEngine {
+    pub fn new() -> Self {
+        Self {
+            board: Board::initial(),
+            color: Color::Dark,
+            depth: DEFAULT_DEPTH,
+        }
+    }
+
+    pub fn run<R: BufRead, W: Write>(&mut self, input: R, mut output: W) -> Result<()> {
+        for line in input.lines() {
+            let line = line?;
+            match self.handle_command(&line, &mut output) {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(Error::Io(e)) => return Err(Error::Io(e)),
+                // the GUI may send anything; bad input is reported and skipped.
+                Err(e) => eprintln!("Ignored `{}`: {}", line, e),
+            }
+            output.flush()?;
+        }
+        Ok(())
+    }
+
+    // Returns false when the GUI asks the engine to quit.
+    fn handle_command<W: Write>(&mut self, line: &str, output: &mut W) -> Result<bool> {
+        let mut split = line.split_whitespace();
+        match split.next() {
+            Some("nboard") => writeln!(output, "set myname {}", ENGINE_NAME)?,
+            Some("set") => self.handle_set(&mut split, line)?,
+            Some("move") => self.handle_move(&mut split)?,
+            Some("go") => self.handle_go(output)?,
+            Some("hint") => {
+                let count = split.next().and_then(|n| n.parse().ok()).unwrap_or(1);
+                self.handle_hint(count, output)?;
+            }
+            Some("ping") => writeln!(output, "pong {}", split.next().unwrap_or("0"))?,
+            // there is no persistent book to learn into, so just acknowledge.
+            Some("learn") => writeln!(output, "learned")?,
+            Some("quit") => return Ok(false),
+            // unknown and unsupported commands (analyze, contempt, ...) are ignored.
+            _ => {}
+        }
+        Ok(true)
+    }
+
+    fn handle_set(&mut self, split: &mut SplitWhitespace, line: &str) -> Result<()> {
+        match split.next() {
+            Some("depth") => {
+                self.depth = split
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .filter(|&depth| depth > 0)
+                    .ok_or_else(|| malformed("While parsing set depth: Invalid depth."))?;
+            }
+            Some("game") => {
+                let ggf = line.splitn(3, ' ').nth(2).unwrap_or("");
+                let (board, color) = parse_ggf(ggf)?;
+                self.board = board;
+                self.color = color;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn handle_move(&mut self, split: &mut SplitWhitespace) -> Result<()> {
+        let mv = split
+            .next()
+            .ok_or_else(|| malformed("While parsing move: Invalid message."))?;
+        self.play(parse_move(mv)?)
+    }
+
+    fn handle_go<W: Write>(&self, output: &mut W) -> Result<()> {
+        writeln!(output, "status Thinking")?;
+        let reply = match self.best_move() {
+            Some((square, Some(score))) => format!("=== {}/{}", square, score),
+            Some((square, None)) => format!("=== {}", square),
+            None => "=== PA".to_string(),
+        };
+        writeln!(output, "status")?;
+        writeln!(output, "{}", reply)?;
+        Ok(())
+    }
+
+    fn handle_hint<W: Write>(&self, count: usize, output: &mut W) -> Result<()> {
+        writeln!(output, "status Analyzing")?;
+        let (mut scores, depth) = self.score_moves();
+        scores.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+        for (square, score) in scores.iter().take(count) {
+            writeln!(output, "search {} {} 0 {}", square, score, depth)?;
+        }
+        writeln!(output, "status")?;
+        Ok(())
+    }
+
+    fn play(&mut self, mv: Move) -> Result<()> {
+        if !mv.is_legal(self.board, self.color) {
+            return Err(Error::IllegalMove(mv));
+        }
+        if let Move::Square(square) = mv {
+            self.board = self.board.flip(square.to_uint(), self.color);
+        }
+        self.color = self.color.opposite();
+        Ok(())
+    }
+
+    // The move to play and its evaluation, the final disc differential once
+    // the endgame is solved.
+    fn best_move(&self) -> Option<(Square, Option<i16>)> {
+        if self.board.flippable_squares(self.color) == 0 {
+            return None;
+        }
+        if self.board.empty_squares_count() as i8 <= self.depth {
+            let search = Exhausive::new(ENDGAME_TIME_LIMIT_MS);
+            if let Some((mv, score)) = search.solve(self.board, self.color) {
+                return mv.square().map(|square| (square, Some(score as i16)));
+            }
+            return self.fallback_move();
+        }
+        self.score_moves()
+            .0
+            .into_iter()
+            .max_by_key(|&(_, score)| score)
+            .map(|(square, score)| (square, Some(score)))
+    }
+
+    // A time limited midgame search, for when the endgame solver runs out of
+    // time.
+    fn fallback_move(&self) -> Option<(Square, Option<i16>)> {
+        let search = NegaScout::new(
+            MIDGAME_TIME_LIMIT_MS,
+            NegaScout::emergency_move(self.board, self.color),
+        );
+        let square = search.next_move(self.board, self.color).square()?;
+        Some((square, search.last_info().map(|info| info.score)))
+    }
+
+    // Scores every move, deepening up to the GUI's depth while time allows.
+    // Returns the scores of the deepest finished search and its depth.
+    fn score_moves(&self) -> (Vec<(Square, i16)>, i8) {
+        let search = NegaScout::new(MIDGAME_TIME_LIMIT_MS, None);
+        let mut ret = (Vec::new(), 0);
+        for depth in 0..=self.depth {
+            let scores = search.score_moves(self.board, self.color, depth);
+            if search.should_stop.load(Ordering::Relaxed) {
+                break;
+            }
+            ret = (scores, depth);
+        }
+        ret
+    }
+}
+
+// Moves look like `F5`, `f5/1.23/0.5` or `PA` for a pass.
+fn parse_move(s: &str) -> Result<Move> {
+    let s = s.split('/').next().unwrap_or("");
+    if s.eq_ignore_ascii_case("PA") {
+        return Ok(Move::Pass);
+    }
+    Move::from_str(s).map_err(|_| Error::BadSquare(s.to_string()))
+}
+
+// Reads the starting position (BO tag) of a GGF game and plays its moves
+// (B and W tags), returning the resulting board and side to move.
+pub fn parse_ggf(ggf: &str) -> Result<(Board, Color)> {
+    let mut board = None;
+    let mut color = Color::Dark;
+    let mut chars = ggf.chars();
+    let mut tag = String::new();
+    while let Some(c) = chars.next() {
+        if c.is_ascii_uppercase() {
+            tag.push(c);
+            continue;
+        }
+        if c != '[' {
+            tag.clear();
+            continue;
+        }
+        let value: String = chars.by_ref().take_while(|&c| c != ']').collect();
+        match tag.as_str() {
+            "BO" => {
+                let (b, c) = parse_ggf_board(&value)?;
+                board = Some(b);
+                color = c;
+            }
+            "B" | "W" => {
+                let b = board.ok_or_else(|| malformed("While parsing game: Move before board."))?;
+                let mover = if tag == "B" {
+                    Color::Dark
+                } else {
+                    Color::Light
+                };
+                let mv = parse_move(&value)?;
+                if let Move::Square(square) = mv {
+                    if !mv.is_legal(b, mover) {
+                        return Err(Error::IllegalMove(mv));
+                    }
+                    board = Some(b.flip(square.to_uint(), mover));
+                }
+                color = mover.opposite();
+            }
+            _ => {}
+        }
+        tag.clear();
+    }
+    board
+        .map(|b| (b, color))
+        .ok_or_else(|| malformed("While parsing game: Missing board."))
+}
+
+// `8 <64 squares of * O -> <side to move>`
+fn parse_ggf_board(s: &str) -> Result<(Board, Color)> {
+    let mut split = s.split_whitespace();
+    if split.next() != Some("8") {
+        return Err(malformed("While parsing game: Unsupported board size."));
+    }
+    let squares: Vec<char> = split.flat_map(|s| s.chars()).collect();
+    if squares.len() != 65 {
+        return Err(malformed("While parsing game: Invalid board."));
+    }
+    let board: String = squares[..64].iter().collect();
+    let board =
+        Board::from_str(&board).map_err(|_| malformed("While parsing game: Invalid square."))?;
+    let color = match squares[64] {
+        '*' => Color::Dark,
+        'O' => Color::Light,
+        _ => return Err(malformed("While parsing game: Invalid side to move.")),
+    };
+    Ok((board, color))
+}
+
+fn malformed(msg: &str) -> Error {
+    Error::MalformedMessage(msg.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const GAME: &str = "set game (;GM[Othello]PC[NBoard]DT[2020-01-01]PB[a]PW[b]RE[?]TI[5:00]TY[8]\
+                        BO[8 ---------------------------O*------*O--------------------------- *]\
+                        B[F5//1.2]W[F6]B[E6];)";
+
+    fn run(commands: &str) -> String {
+        let mut output = Vec::new();
+        NBoardEngine::new()
+            .run(Cursor::new(commands.to_string()), &mut output)
+            .unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn ggf() {
+        let (board, color) = parse_ggf(&GAME["set game ".len()..]).unwrap();
+        let expected = Board::initial()
+            .flip(Square::from_str("F5").unwrap().to_uint(), Color::Dark)
+            .flip(Square::from_str("F6").unwrap().to_uint(), Color::Light)
+            .flip(Square::from_str("E6").unwrap().to_uint(), Color::Dark);
+        assert_eq!(board, expected);
+        assert_eq!(color, Color::Light);
+    }
+
+    #[test]
+    fn ping() {
+        assert_eq!(run("nboard 2\nping 3\n"), "set myname reversi_rs\npong 3\n");
+    }
+
+    #[test]
+    fn go() {
+        let output = run(&format!("{}\nset depth 2\nmove F4\ngo\n", GAME));
+        let reply = output.lines().last().unwrap();
+        assert!(reply.starts_with("=== "));
+        let (board, _) = parse_ggf(&GAME["set game ".len()..]).unwrap();
+        let board = board.flip(Square::from_str("F4").unwrap().to_uint(), Color::Light);
+        let square = Square::from_str(reply[4..].split('/').next().unwrap()).unwrap();
+        assert_ne!(
+            board.flippable_squares(Color::Dark) & 1 << square.to_uint(),
+            0
+        );
+    }
+
+    #[test]
+    fn hint() {
+        let output = run("set depth 1\nhint 2\n");
+        let searches: Vec<&str> = output.lines().filter(|l| l.starts_with("search")).collect();
+        assert_eq!(searches.len(), 2);
+    }
+
+    #[test]
+    fn bad_input() {
+        let output = run("move A1\nset depth x\nset game (;GM[Othello]BO[9];)\nping 4\n");
+        assert_eq!(output, "pong 4\n");
+    }
+}
@@ -0,0 +1,225 @@
+use std::fmt::Write;
+
+use crate::game::base::Color;
+use crate::game::board::Board;
+use crate::game::square::Square;
+
+const RESET: &str = "\x1b[0m";
+const BOARD_BG: &str = "\x1b[42m";
+const LAST_MOVE_BG: &str = "\x1b[43m";
+const DARK_FG: &str = "\x1b[30m";
+const LIGHT_FG: &str = "\x1b[97m";
+const HINT_FG: &str = "\x1b[33m";
+
+const CELL: u32 = 40;
+const MARGIN: u32 = 24;
+
+// What to draw: the board, and optionally whose turn it is (legal moves are
+// marked for that side) and the move that led to it.
+#[derive(Clone, Copy, Debug)]
+pub struct BoardView {
+    pub board: Board,
+    pub side_to_move: Option<Color>,
+    pub last_move: Option<Square>,
+}
+
+impl BoardView {
+    pub fn new(board: Board) -> Self {
+        Self {
+            board,
+            side_to_move: None,
+            last_move: None,
+        }
+    }
+
+    fn hints(&self) -> u64 {
+        self.side_to_move
+            .map(|color| self.board.flippable_squares(color))
+            .unwrap_or(0)
+    }
+
+    fn summary(&self) -> String {
+        let mut ret = format!(
+            "dark {} - light {}",
+            self.board.dark().count_ones(),
+            self.board.light().count_ones()
+        );
+        if let Some(color) = self.side_to_move {
+            let name = match color {
+                Color::Dark => "dark",
+                Color::Light => "light",
+            };
+            write!(ret, ", {} to move", name).unwrap();
+        }
+        ret
+    }
+
+    // For a terminal that understands ANSI colors.
+    pub fn ansi(&self) -> String {
+        let hints = self.hints();
+        let last_move = self.last_move.map(|s| s.to_uint());
+        let mut ret = String::from("  A B C D E F G H\n");
+        for y in 0..8 {
+            write!(ret, "{} {}", y + 1, BOARD_BG).unwrap();
+            for x in 0..8 {
+                let i = x + y * 8;
+                let bit = 1u64 << i;
+                if last_move == Some(i) {
+                    ret.push_str(LAST_MOVE_BG);
+                }
+                if self.board.dark() & bit != 0 {
+                    write!(ret, "{}\u{25cf}", DARK_FG).unwrap();
+                } else if self.board.light() & bit != 0 {
+                    write!(ret, "{}\u{25cf}", LIGHT_FG).unwrap();
+                } else if hints & bit != 0 {
+                    write!(ret, "{}\u{00b7}", HINT_FG).unwrap();
+                } else {
+                    ret.push(' ');
+                }
+                if last_move == Some(i) {
+                    ret.push_str(BOARD_BG);
+                }
+                ret.push(' ');
+            }
+            writeln!(ret, "{}", RESET).unwrap();
+        }
+        writeln!(ret, "{}", self.summary()).unwrap();
+        ret
+    }
+
+    pub fn svg(&self) -> String {
+        let hints = self.hints();
+        let size = CELL * 8 + MARGIN * 2;
+        let mut ret = String::new();
+        writeln!(
+            ret,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}">"#,
+            size,
+            size + MARGIN
+        )
+        .unwrap();
+        writeln!(
+            ret,
+            r##"<rect x="{0}" y="{0}" width="{1}" height="{1}" fill="#2e8b57" stroke="black"/>"##,
+            MARGIN,
+            CELL * 8
+        )
+        .unwrap();
+        for i in 1..8 {
+            let offset = MARGIN + CELL * i;
+            writeln!(
+                ret,
+                r#"<line x1="{0}" y1="{1}" x2="{0}" y2="{2}" stroke="black"/>"#,
+                offset,
+                MARGIN,
+                MARGIN + CELL * 8
+            )
+            .unwrap();
+            writeln!(
+                ret,
+                r#"<line x1="{1}" y1="{0}" x2="{2}" y2="{0}" stroke="black"/>"#,
+                offset,
+                MARGIN,
+                MARGIN + CELL * 8
+            )
+            .unwrap();
+        }
+        for i in 0..8 {
+            let center = MARGIN + CELL * i + CELL / 2;
+            writeln!(
+                ret,
+                r#"<text x="{}" y="{}" text-anchor="middle" font-size="14">{}</text>"#,
+                center,
+                MARGIN - 8,
+                (b'A' + i as u8) as char
+            )
+            .unwrap();
+            writeln!(
+                ret,
+                r#"<text x="{}" y="{}" text-anchor="middle" font-size="14">{}</text>"#,
+                MARGIN / 2,
+                center + 5,
+                i + 1
+            )
+            .unwrap();
+        }
+        if let Some(square) = self.last_move {
+            writeln!(
+                ret,
+                r#"<rect x="{}" y="{}" width="{2}" height="{2}" fill="none" stroke="red" stroke-width="3"/>"#,
+                MARGIN + CELL * square.x as u32,
+                MARGIN + CELL * square.y as u32,
+                CELL
+            )
+            .unwrap();
+        }
+        for i in 0..64 {
+            let bit = 1u64 << i;
+            let cx = MARGIN + CELL * (i % 8) + CELL / 2;
+            let cy = MARGIN + CELL * (i / 8) + CELL / 2;
+            let (radius, fill) = if self.board.dark() & bit != 0 {
+                (CELL * 2 / 5, "black")
+            } else if self.board.light() & bit != 0 {
+                (CELL * 2 / 5, "white")
+            } else if hints & bit != 0 {
+                (CELL / 8, "yellow")
+            } else {
+                continue;
+            };
+            writeln!(
+                ret,
+                r#"<circle cx="{}" cy="{}" r="{}" fill="{}" stroke="black"/>"#,
+                cx, cy, radius, fill
+            )
+            .unwrap();
+        }
+        writeln!(
+            ret,
+            r#"<text x="{}" y="{}" font-size="14">{}</text>"#,
+            MARGIN,
+            size + MARGIN / 2,
+            self.summary()
+        )
+        .unwrap();
+        ret.push_str("</svg>\n");
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn view() -> BoardView {
+        let square = Square::from_str("F5").unwrap();
+        BoardView {
+            board: Board::initial().flip(square.to_uint(), Color::Dark),
+            side_to_move: Some(Color::Light),
+            last_move: Some(square),
+        }
+    }
+
+    #[test]
+    fn ansi() {
+        let s = view().ansi();
+        assert_eq!(s.lines().count(), 10);
+        assert_eq!(s.matches('\u{25cf}').count(), 5);
+        assert_eq!(s.matches('\u{00b7}').count(), 3);
+        assert_eq!(s.matches(LAST_MOVE_BG).count(), 1);
+        assert!(s.ends_with("dark 4 - light 1, light to move\n"));
+
+        let s = BoardView::new(Board::initial()).ansi();
+        assert_eq!(s.matches('\u{00b7}').count(), 0);
+        assert!(s.ends_with("dark 2 - light 2\n"));
+    }
+
+    #[test]
+    fn svg() {
+        let s = view().svg();
+        assert!(s.starts_with("<svg"));
+        assert!(s.ends_with("</svg>\n"));
+        assert_eq!(s.matches("<circle").count(), 5 + 3);
+        assert_eq!(s.matches(r#"stroke="red""#).count(), 1);
+    }
+}
@@ -0,0 +1,477 @@
+use std::cmp;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::str::{FromStr, SplitWhitespace};
+use std::time::{Duration, Instant};
+
+use crate::error::{Error, Result};
+use crate::game::base::{Color, GameResult};
+use crate::game::board::Board;
+use crate::game::moves::Move;
+use crate::message::{
+    ack_message, bye_message, end_message, move_message, start_message, ClientMessage,
+};
+
+// Reference implementation of the contest server. It accepts exactly two
+// clients, plays `game_count` games between them alternating colors and
+// reports the overall result with BYE.
+pub struct Server {
+    listener: TcpListener,
+    game_count: u32,
+    time_limit_ms: i32,
+}
+
+struct Connection {
+    name: String,
+    reader: BufReader<TcpStream>,
+    writer: BufWriter<TcpStream>,
+    win_count: u32,
+    lose_count: u32,
+    tie_count: u32,
+}
+
+impl Server {
+    pub fn bind(addr: &str, game_count: u32, time_limit_ms: i32) -> Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        Ok(Self {
+            listener,
+            game_count,
+            time_limit_ms,
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    pub fn run(self) -> Result<()> {
+        let mut players = [self.accept()?, self.accept()?];
+        for i in 0..self.game_count {
+            let black = (i % 2) as usize;
+            println!(
+                "Game {}: {} (BLACK) vs {} (WHITE)",
+                i + 1,
+                players[black].name,
+                players[1 - black].name
+            );
+            if let Err(e) = self.play_game(&mut players, black) {
+                // whoever is still connected learns that the match is over.
+                let _ = Self::send_bye(&mut players);
+                return Err(e);
+            }
+        }
+        Self::send_bye(&mut players)
+    }
+
+    // Tries both players even if one of them is gone.
+    fn send_bye(players: &mut [Connection; 2]) -> Result<()> {
+        let stat = format!("{} {}", players[0].stat(), players[1].stat());
+        let mut ret = Ok(());
+        for player in players.iter_mut() {
+            ret = ret.and(player.send_message(&bye_message(&stat)));
+        }
+        ret
+    }
+
+    fn accept(&self) -> Result<Connection> {
+        let (stream, addr) = self.listener.accept()?;
+        println!("Accepted connection from {}", addr);
+        let mut conn = Connection::new(stream)?;
+        match conn.poll_message()? {
+            Ok(ClientMessage::Open { name }) => {
+                conn.name = name;
+                Ok(conn)
+            }
+            _ => Err(Error::UnexpectedMessage { expected: "Open" }),
+        }
+    }
+
+    // A player whose connection fails loses the game, and the other one is
+    // still told about it before the error is returned.
+    fn play_game(&self, players: &mut [Connection; 2], black: usize) -> Result<()> {
+        let mut board = Board::initial();
+        match self.play_moves(players, black, &mut board) {
+            Ok((winner, reason)) => Self::finish(players, black, board, winner, reason),
+            Err((lost, e)) => {
+                println!("Lost connection to {}: {}", players[lost].name, e);
+                let winner = if lost == black {
+                    Color::Light
+                } else {
+                    Color::Dark
+                };
+                let _ = Self::finish(players, black, board, Some(winner), "DISCONNECTED");
+                Err(e)
+            }
+        }
+    }
+
+    // Plays the game out on `board`, returning the winner and the reason the
+    // game ended, or the player whose connection failed.
+    fn play_moves(
+        &self,
+        players: &mut [Connection; 2],
+        black: usize,
+        board: &mut Board,
+    ) -> std::result::Result<(Option<Color>, &'static str), (usize, Error)> {
+        let white = 1 - black;
+        let mut times = [self.time_limit_ms; 2];
+        let black_start = start_message(Color::Dark, &players[white].name, times[black]);
+        let white_start = start_message(Color::Light, &players[black].name, times[white]);
+        players[black]
+            .send_message(&black_start)
+            .map_err(|e| (black, e))?;
+        players[white]
+            .send_message(&white_start)
+            .map_err(|e| (white, e))?;
+
+        let mut color = Color::Dark;
+        loop {
+            let (cur, other) = match color {
+                Color::Dark => (black, white),
+                Color::Light => (white, black),
+            };
+            let now = Instant::now();
+            players[cur]
+                .set_time_left(times[cur])
+                .map_err(|e| (cur, e))?;
+            let msg = match players[cur].poll_message() {
+                Err(Error::Io(ref e)) if is_timeout(e) => {
+                    return Ok((Some(color.opposite()), "TIMEOUT"));
+                }
+                msg => msg.map_err(|e| (cur, e))?,
+            };
+            times[cur] -= now.elapsed().as_millis() as i32;
+            if times[cur] < 0 {
+                return Ok((Some(color.opposite()), "TIMEOUT"));
+            }
+
+            let reply = match msg {
+                Ok(ClientMessage::Move { pos }) if pos.is_legal(*board, color) => {
+                    if let Move::Square(square) = pos {
+                        *board = board.flip(square.to_uint(), color);
+                    }
+                    move_message(pos)
+                }
+                _ => return Ok((Some(color.opposite()), "ILLEGAL_MOVE")),
+            };
+
+            if board.flippable_squares(Color::Dark) == 0
+                && board.flippable_squares(Color::Light) == 0
+            {
+                return Ok((board.winner(), "DOUBLE_PASS"));
+            }
+
+            players[cur]
+                .send_message(&ack_message(times[cur]))
+                .map_err(|e| (cur, e))?;
+            players[other]
+                .send_message(&reply)
+                .map_err(|e| (other, e))?;
+            color = color.opposite();
+        }
+    }
+
+    // Sends END to both players even if one of them is gone.
+    fn finish(
+        players: &mut [Connection; 2],
+        black: usize,
+        board: Board,
+        winner: Option<Color>,
+        reason: &str,
+    ) -> Result<()> {
+        println!(
+            "Game ended: {:?} wins ({}). dark: {}, light: {}",
+            winner,
+            reason,
+            board.dark().count_ones(),
+            board.light().count_ones()
+        );
+        let mut ret = Ok(());
+        for &(i, color) in [(black, Color::Dark), (1 - black, Color::Light)].iter() {
+            let (player_board, op_board) = board.target_boards(color);
+            let result = match winner {
+                Some(c) if c == color => GameResult::Win,
+                Some(_) => GameResult::Lose,
+                None => GameResult::Tie,
+            };
+            let player = &mut players[i];
+            match result {
+                GameResult::Win => player.win_count += 1,
+                GameResult::Lose => player.lose_count += 1,
+                GameResult::Tie => player.tie_count += 1,
+            }
+            ret = ret.and(player.send_message(&end_message(
+                result,
+                player_board.count_ones() as u8,
+                op_board.count_ones() as u8,
+                reason,
+            )));
+        }
+        ret
+    }
+}
+
+// What a read past the socket's timeout fails with, depending on the platform.
+fn is_timeout(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> Result<Self> {
+        let w_stream = stream.try_clone()?;
+        Ok(Self {
+            name: String::new(),
+            reader: BufReader::new(stream),
+            writer: BufWriter::new(w_stream),
+            win_count: 0,
+            lose_count: 0,
+            tie_count: 0,
+        })
+    }
+
+    // Makes `poll_message` give up once the player's clock runs out.
+    fn set_time_left(&mut self, time_ms: i32) -> Result<()> {
+        // a zero timeout would mean no timeout at all.
+        let time_ms = cmp::max(time_ms, 1) as u64;
+        self.reader
+            .get_ref()
+            .set_read_timeout(Some(Duration::from_millis(time_ms)))?;
+        Ok(())
+    }
+
+    // The outer error is a connection failure, the inner one a malformed message.
+    fn poll_message(&mut self) -> Result<Result<ClientMessage>> {
+        let mut buf = String::new();
+        if self.reader.read_line(&mut buf)? == 0 {
+            return Err(Error::ConnectionClosed);
+        }
+        print!("Read from {}: {}", self.name, buf);
+        Ok(parse_input(&buf))
+    }
+
+    fn send_message(&mut self, msg: &str) -> Result<()> {
+        writeln!(self.writer, "{}", msg)?;
+        self.writer.flush()?;
+        println!("Sent to {}: {}", self.name, msg);
+        Ok(())
+    }
+
+    fn stat(&self) -> String {
+        format!(
+            "{} {} {} {}",
+            self.name,
+            self.win_count as i64 - self.lose_count as i64,
+            self.win_count,
+            self.lose_count
+        )
+    }
+}
+
+pub fn parse_input(buf: &str) -> Result<ClientMessage> {
+    let mut split = buf.split_whitespace();
+    match split.next() {
+        Some("OPEN") => parse_open(&mut split),
+        Some("MOVE") => parse_move(&mut split),
+        Some(cmd) => Err(Error::UnknownCommand(cmd.to_string())),
+        None => Err(Error::MalformedMessage("Empty message.".to_string())),
+    }
+}
+
+fn parse_open(split: &mut SplitWhitespace) -> Result<ClientMessage> {
+    split
+        .next()
+        .ok_or_else(|| Error::MalformedMessage("While parsing open: Invalid message.".to_string()))
+        .map(|name| ClientMessage::Open {
+            name: name.to_string(),
+        })
+}
+
+fn parse_move(split: &mut SplitWhitespace) -> Result<ClientMessage> {
+    match split.next() {
+        Some(s) => Move::from_str(s)
+            .map(|pos| ClientMessage::Move { pos })
+            .map_err(|_| Error::BadSquare(s.to_string())),
+        None => Err(Error::MalformedMessage(
+            "While parsing move: Invalid message.".to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::Client;
+    use crate::game::strategy::{Naive, Strategy};
+    use crate::message::{open_message, ServerMessage};
+    use std::sync::mpsc;
+    use std::thread;
+
+    fn spawn_server(game_count: u32, time_limit_ms: i32) -> (u32, thread::JoinHandle<Result<()>>) {
+        let server = Server::bind("127.0.0.1:0", game_count, time_limit_ms).unwrap();
+        let port = server.local_addr().unwrap().port() as u32;
+        (port, thread::spawn(move || server.run()))
+    }
+
+    fn connect(port: u32, name: &str) -> Client {
+        let mut client = Client::new("127.0.0.1", port).unwrap();
+        client.send_message(&open_message(name)).unwrap();
+        client
+    }
+
+    // Runs `f` with a client on its own thread, returning once the client has
+    // sent OPEN so that it is accepted before any client connected later.
+    fn spawn_client<T, F>(port: u32, name: &'static str, f: F) -> thread::JoinHandle<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(Client) -> T + Send + 'static,
+    {
+        let (opened, wait) = mpsc::channel();
+        let handle = thread::spawn(move || {
+            let client = connect(port, name);
+            opened.send(()).unwrap();
+            f(client)
+        });
+        wait.recv().unwrap();
+        handle
+    }
+
+    fn play_naive(mut client: Client) -> Vec<(GameResult, u8, u8, String)> {
+        let mut board = Board::initial();
+        let mut color = Color::Dark;
+        let mut results = Vec::new();
+        let play = |client: &mut Client, board: &mut Board, color: Color| {
+            let mv = Naive().next_move(*board, color);
+            if let Move::Square(square) = mv {
+                *board = board.flip(square.to_uint(), color);
+            }
+            client.send_move(mv).unwrap();
+        };
+        loop {
+            match client.poll_message().unwrap() {
+                ServerMessage::Start { color: c, .. } => {
+                    board = Board::initial();
+                    color = c;
+                    if color == Color::Dark {
+                        play(&mut client, &mut board, color);
+                    }
+                }
+                ServerMessage::Move { pos } => {
+                    if let Move::Square(square) = pos {
+                        board = board.flip(square.to_uint(), color.opposite());
+                    }
+                    play(&mut client, &mut board, color);
+                }
+                ServerMessage::Ack { .. } => {}
+                ServerMessage::End {
+                    result,
+                    player_count,
+                    op_count,
+                    reason,
+                } => results.push((result, player_count, op_count, reason)),
+                ServerMessage::Bye { .. } => return results,
+            }
+        }
+    }
+
+    #[test]
+    fn full_match() {
+        let (port, server) = spawn_server(2, 60000);
+        let first = spawn_client(port, "first", play_naive);
+        let second = play_naive(connect(port, "second"));
+        let first = first.join().unwrap();
+        server.join().unwrap().unwrap();
+
+        assert_eq!(first.len(), 2);
+        assert_eq!(second.len(), 2);
+        for (a, b) in first.iter().zip(second.iter()) {
+            assert_eq!(a.1, b.2);
+            assert_eq!(a.2, b.1);
+            assert_eq!(a.3, "DOUBLE_PASS");
+            let expected = match a.0 {
+                GameResult::Win => GameResult::Lose,
+                GameResult::Lose => GameResult::Win,
+                GameResult::Tie => GameResult::Tie,
+            };
+            assert_eq!(b.0, expected);
+        }
+    }
+
+    #[test]
+    fn illegal_move() {
+        let (port, server) = spawn_server(1, 60000);
+        let black = spawn_client(port, "black", |mut client| {
+            client.poll_message().unwrap();
+            client.send_message("MOVE A1").unwrap();
+            let ret = match client.poll_message().unwrap() {
+                ServerMessage::End { result, reason, .. } => (result, reason),
+                _ => panic!("expected End"),
+            };
+            client.poll_message().unwrap();
+            ret
+        });
+        let mut white = connect(port, "white");
+        white.poll_message().unwrap();
+        match white.poll_message().unwrap() {
+            ServerMessage::End { result, .. } => assert_eq!(result, GameResult::Win),
+            _ => panic!("expected End"),
+        }
+        let (result, reason) = black.join().unwrap();
+        assert_eq!(result, GameResult::Lose);
+        assert_eq!(reason, "ILLEGAL_MOVE");
+        white.poll_message().unwrap();
+        server.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn timeout() {
+        let (port, server) = spawn_server(1, 200);
+        // black never answers START.
+        let black = spawn_client(port, "black", |mut client| {
+            client.poll_message().unwrap();
+            let ret = match client.poll_message().unwrap() {
+                ServerMessage::End { result, reason, .. } => (result, reason),
+                _ => panic!("expected End"),
+            };
+            client.poll_message().unwrap();
+            ret
+        });
+        let mut white = connect(port, "white");
+        white.poll_message().unwrap();
+        match white.poll_message().unwrap() {
+            ServerMessage::End { result, .. } => assert_eq!(result, GameResult::Win),
+            _ => panic!("expected End"),
+        }
+        let (result, reason) = black.join().unwrap();
+        assert_eq!(result, GameResult::Lose);
+        assert_eq!(reason, "TIMEOUT");
+        white.poll_message().unwrap();
+        server.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn disconnect() {
+        let (port, server) = spawn_server(1, 60000);
+        // black leaves as soon as the game starts.
+        let black = spawn_client(port, "black", |mut client| {
+            client.poll_message().unwrap();
+        });
+        let mut white = connect(port, "white");
+        white.poll_message().unwrap();
+        black.join().unwrap();
+        match white.poll_message().unwrap() {
+            ServerMessage::End { result, reason, .. } => {
+                assert_eq!(result, GameResult::Win);
+                assert_eq!(reason, "DISCONNECTED");
+            }
+            _ => panic!("expected End"),
+        }
+        assert!(matches!(
+            white.poll_message().unwrap(),
+            ServerMessage::Bye { .. }
+        ));
+        assert!(server.join().unwrap().is_err());
+    }
+}
@@ -0,0 +1,242 @@
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::net::TcpStream;
+use std::path::Path;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::error::{Error, Result};
+
+// Line based connection to the game server.
+pub trait Transport {
+    // Reads one line including the trailing newline. Returns 0 on EOF.
+    fn read_line(&mut self, buf: &mut String) -> Result<usize>;
+    // Writes one line and returns the line the server actually received.
+    fn write_line(&mut self, line: &str) -> Result<String>;
+    fn reconnect(&mut self) -> Result<()>;
+
+    // Lines written that differ from a recording being replayed.
+    fn mismatch_count(&self) -> usize {
+        0
+    }
+}
+
+pub struct TcpTransport {
+    host: String,
+    port: u32,
+    reader: BufReader<TcpStream>,
+    writer: BufWriter<TcpStream>,
+}
+
+impl TcpTransport {
+    pub fn connect(host: &str, port: u32) -> Result<Self> {
+        let (reader, writer) = Self::open(host, port)?;
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            reader,
+            writer,
+        })
+    }
+
+    fn open(host: &str, port: u32) -> Result<(BufReader<TcpStream>, BufWriter<TcpStream>)> {
+        let addr = host.to_string() + ":" + &port.to_string();
+        let r_stream = TcpStream::connect(addr)?;
+        let w_stream = r_stream.try_clone()?;
+        Ok((BufReader::new(r_stream), BufWriter::new(w_stream)))
+    }
+}
+
+impl Transport for TcpTransport {
+    fn read_line(&mut self, buf: &mut String) -> Result<usize> {
+        Ok(self.reader.read_line(buf)?)
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<String> {
+        writeln!(self.writer, "{}", line)?;
+        self.writer.flush()?;
+        Ok(line.to_string())
+    }
+
+    fn reconnect(&mut self) -> Result<()> {
+        let (reader, writer) = Self::open(&self.host, self.port)?;
+        self.reader = reader;
+        self.writer = writer;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Direction {
+    Received,
+    Sent,
+}
+
+// Writes every exchanged line as `<elapsed ms>\t<R|S>\t<line>`.
+pub struct SessionLog {
+    writer: BufWriter<File>,
+    start: Instant,
+}
+
+impl SessionLog {
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        let since_epoch = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or(0);
+        writeln!(
+            writer,
+            "# session started at {} ms since epoch",
+            since_epoch
+        )?;
+        writer.flush()?;
+        Ok(Self {
+            writer,
+            start: Instant::now(),
+        })
+    }
+
+    pub fn record(&mut self, direction: Direction, line: &str) -> Result<()> {
+        let direction = match direction {
+            Direction::Received => "R",
+            Direction::Sent => "S",
+        };
+        writeln!(
+            self.writer,
+            "{}\t{}\t{}",
+            self.start.elapsed().as_millis(),
+            direction,
+            line.trim_end()
+        )?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+pub fn parse_session_log(contents: &str) -> Result<Vec<(Direction, String)>> {
+    contents
+        .lines()
+        .filter(|line| !line.starts_with('#') && !line.trim().is_empty())
+        .map(|line| {
+            let mut split = line.splitn(3, '\t');
+            let _elapsed = split.next();
+            let direction = match split.next() {
+                Some("R") => Direction::Received,
+                Some("S") => Direction::Sent,
+                _ => {
+                    return Err(Error::MalformedMessage(format!(
+                        "Invalid log line: {}",
+                        line
+                    )))
+                }
+            };
+            let line = split.next().unwrap_or("").to_string();
+            Ok((direction, line))
+        })
+        .collect()
+}
+
+// Feeds the received lines of a session log back to the client. Lines sent by
+// the client are compared against the recorded ones, and the recorded line is
+// reported as delivered so the game follows the recorded course.
+pub struct ReplayTransport {
+    entries: Vec<(Direction, String)>,
+    received_pos: usize,
+    sent_pos: usize,
+    mismatch_count: usize,
+}
+
+impl ReplayTransport {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        Ok(Self::new(parse_session_log(&contents)?))
+    }
+
+    pub fn new(entries: Vec<(Direction, String)>) -> Self {
+        Self {
+            entries,
+            received_pos: 0,
+            sent_pos: 0,
+            mismatch_count: 0,
+        }
+    }
+
+    // Name used in the recorded OPEN message, if any.
+    pub fn recorded_name(&self) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(d, l)| *d == Direction::Sent && l.starts_with("OPEN "))
+            .map(|(_, l)| l["OPEN ".len()..].trim())
+    }
+
+    fn next_entry(&self, pos: usize, direction: Direction) -> Option<usize> {
+        (pos..self.entries.len()).find(|&i| self.entries[i].0 == direction)
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn read_line(&mut self, buf: &mut String) -> Result<usize> {
+        match self.next_entry(self.received_pos, Direction::Received) {
+            Some(i) => {
+                self.received_pos = i + 1;
+                let line = &self.entries[i].1;
+                buf.push_str(line);
+                buf.push('\n');
+                Ok(line.len() + 1)
+            }
+            None => Ok(0),
+        }
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<String> {
+        match self.next_entry(self.sent_pos, Direction::Sent) {
+            Some(i) => {
+                self.sent_pos = i + 1;
+                let recorded = &self.entries[i].1;
+                if recorded != line {
+                    self.mismatch_count += 1;
+                    println!("Replay: sent `{}`, recorded `{}`", line, recorded);
+                }
+                Ok(recorded.clone())
+            }
+            None => Err(Error::ConnectionClosed),
+        }
+    }
+
+    fn reconnect(&mut self) -> Result<()> {
+        Err(Error::ConnectionClosed)
+    }
+
+    fn mismatch_count(&self) -> usize {
+        self.mismatch_count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replay() {
+        let log = "# session started at 0 ms since epoch\n\
+                   0\tS\tOPEN Joe\n\
+                   3\tR\tSTART BLACK op 1000\n\
+                   9\tS\tMOVE F5\n\
+                   10\tR\tACK 990\n";
+        let mut transport = ReplayTransport::new(parse_session_log(log).unwrap());
+        assert_eq!(transport.recorded_name(), Some("Joe"));
+        assert_eq!(transport.write_line("OPEN Joe").unwrap(), "OPEN Joe");
+
+        let mut buf = String::new();
+        transport.read_line(&mut buf).unwrap();
+        assert_eq!(buf, "START BLACK op 1000\n");
+
+        assert_eq!(transport.write_line("MOVE D3").unwrap(), "MOVE F5");
+        assert_eq!(transport.mismatch_count(), 1);
+
+        let mut buf = String::new();
+        transport.read_line(&mut buf).unwrap();
+        assert_eq!(buf, "ACK 990\n");
+        assert_eq!(transport.read_line(&mut String::new()).unwrap(), 0);
+    }
+}